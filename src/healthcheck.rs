@@ -0,0 +1,25 @@
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Pings are best effort, so they mustn't hold up the run for long.
+const PING_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Pings the healthchecks.io-style check URL, or its /fail endpoint if the run
+/// failed.
+pub async fn ping_healthcheck(client: &reqwest::Client, url: &str, success: bool) {
+    let url = if success {
+        String::from(url)
+    } else {
+        format!("{}/fail", url.trim_end_matches('/'))
+    };
+    let result = client
+        .get(&url)
+        .timeout(PING_TIMEOUT)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status());
+    match result {
+        Ok(_) => debug!(url, "Pinged healthcheck"),
+        Err(e) => warn!("Unable to ping healthcheck {url}: {e}"),
+    }
+}
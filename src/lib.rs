@@ -0,0 +1,1210 @@
+//! Keeps DNS records pointed at the external IP address of the host.
+//!
+//! [`run`] is the whole dyndns-rs program, configured from its [`Args`], the
+//! environment and the config file. To embed a single provider instead, build
+//! its updater from the [`providers`] module and pass it the record to set:
+//!
+//! ```no_run
+//! use dyndns_rs::providers::{DNSUpdater, DnsRecord, DuckDnsUpdater, RecordType};
+//!
+//! # async fn example() -> Result<(), dyndns_rs::DNSUpdateError> {
+//! let updater = DuckDnsUpdater::new(reqwest::Client::new(), String::from("token"));
+//! let record = DnsRecord::new("myhost.duckdns.org", RecordType::A, "203.0.113.7", 300);
+//! updater.update(record).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use aws_sdk_route53::error::ProvideErrorMetadata;
+pub use config::Args;
+use config::{CompareSource, Config, Output, Target};
+use futures_util::future::join_all;
+use healthcheck::ping_healthcheck;
+use metrics::{serve_metrics, Metrics};
+use ntfy::notify_ntfy;
+use providers::{build_updater, DNSUpdater, DnsRecord, Provider, RecordType};
+use rate_limit::RateLimiter;
+use resolver::{resolver, AddressFamily, Resolver};
+use retry::Backoff;
+use selftest::{checklist, failures, selftest};
+use sources::{ip_source, FixedSource, IpError, IpSource, SuffixSource};
+use state::{load_state, save_state, PushedRecord, SetRecord, State};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{error, fmt, future, io, net, str};
+use summary::{HostResult, HostStatus, RecordSummary, RunSummary};
+use tokio::signal::unix::{signal, Signal, SignalKind};
+use tokio::sync::{watch, OnceCell};
+use tracing::{debug, error, info, info_span, warn, Instrument};
+use webhook::{notify_webhook, Event};
+
+pub mod config;
+mod healthcheck;
+mod metrics;
+mod ntfy;
+pub mod providers;
+mod rate_limit;
+pub mod resolver;
+pub mod retry;
+pub mod selftest;
+pub mod sources;
+pub mod state;
+mod summary;
+mod webhook;
+mod xml;
+
+/// The version of dyndns-rs, as --version prints it.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Sent with every HTTP request, so that services and bug reports can tell
+/// which build made it. Some services block clients without one.
+pub const USER_AGENT: &str = concat!("dyndns-rs/", env!("CARGO_PKG_VERSION"));
+
+#[derive(Debug)]
+pub enum DNSUpdateError {
+    Config(String),
+    Detect(IpError),
+    Lookup(String, io::Error),
+    Route53(Box<aws_sdk_route53::Error>),
+    DigitalOcean(reqwest::Error),
+    GoogleDns(Box<dyn error::Error + Send + Sync>),
+    Gandi(reqwest::Error),
+    Hetzner(reqwest::Error),
+    DuckDns(Box<dyn error::Error + Send + Sync>),
+    Namecheap(Box<dyn error::Error + Send + Sync>),
+    Rfc2136(Box<dyn error::Error + Send + Sync>),
+    /// A failure at a provider with an HTTP API, with the status of its
+    /// response if there was one
+    Http {
+        provider: Provider,
+        status: Option<u16>,
+        message: String,
+    },
+    /// The failure of a batch of updates, given to each record but the first,
+    /// which gets the error itself, with whether that error is worth retrying
+    Batch {
+        host_name: String,
+        message: String,
+        retryable: bool,
+    },
+    AddrParse(net::AddrParseError),
+    /// The number of records that differ from the external IP address in
+    /// check mode
+    Drift(usize),
+    /// The number of checks that failed in self-test mode
+    SelfTest(usize),
+    /// The run took longer than RUN_TIMEOUT
+    Timeout(Duration),
+}
+
+/// Returns whether a failed HTTP request is worth retrying: connection
+/// failures, timeouts, throttling and server errors.
+fn is_retryable_http(e: &reqwest::Error) -> bool {
+    match e.status() {
+        Some(status) => {
+            status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        }
+        None => e.is_connect() || e.is_timeout() || e.is_request(),
+    }
+}
+
+fn is_retryable_boxed(e: &(dyn error::Error + Send + Sync + 'static)) -> bool {
+    e.downcast_ref::<reqwest::Error>()
+        .is_some_and(is_retryable_http)
+}
+
+impl DNSUpdateError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            Self::Config(_)
+            | Self::AddrParse(_)
+            | Self::Drift(_)
+            | Self::SelfTest(_)
+            | Self::Timeout(_) => false,
+            Self::Detect(_) | Self::Lookup(_, _) => true,
+            // Transport failures carry no error code
+            Self::Route53(e) => matches!(
+                e.code(),
+                None | Some(
+                    "PriorRequestNotComplete"
+                        | "Throttling"
+                        | "ThrottlingException"
+                        | "ServiceUnavailable"
+                        | "InternalFailure"
+                )
+            ),
+            Self::DigitalOcean(e) | Self::Gandi(e) | Self::Hetzner(e) => is_retryable_http(e),
+            Self::GoogleDns(e) | Self::DuckDns(e) | Self::Namecheap(e) | Self::Rfc2136(e) => {
+                is_retryable_boxed(e.as_ref())
+            }
+            // Without a response the request never got through
+            Self::Http { status: None, .. } => true,
+            Self::Http {
+                status: Some(status),
+                ..
+            } => *status >= 500 || *status == 429,
+            Self::Batch { retryable, .. } => *retryable,
+        }
+    }
+
+    /// Returns the exit code of the program when it fails with the error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Config(_) => 2,
+            Self::Detect(_) | Self::Lookup(_, _) => 3,
+            Self::Route53(_)
+            | Self::DigitalOcean(_)
+            | Self::GoogleDns(_)
+            | Self::Gandi(_)
+            | Self::Hetzner(_)
+            | Self::DuckDns(_)
+            | Self::Namecheap(_)
+            | Self::Rfc2136(_)
+            | Self::Http { .. }
+            | Self::Batch { .. }
+            | Self::AddrParse(_) => 4,
+            Self::Drift(_) => 5,
+            Self::SelfTest(_) => 6,
+            Self::Timeout(_) => 7,
+        }
+    }
+}
+
+impl fmt::Display for DNSUpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Config(message) => write!(f, "{message}"),
+            Self::Detect(e) => write!(f, "Unable to get current IP address: {e}"),
+            Self::Lookup(host_name, e) => {
+                write!(f, "Unable to get IP address of host {host_name}: {e}")
+            }
+            Self::Route53(e) => write!(f, "Route 53 error: {e}"),
+            Self::DigitalOcean(e) => write!(f, "DigitalOcean error: {e}"),
+            Self::GoogleDns(e) => write!(f, "Google Cloud DNS error: {e}"),
+            Self::Gandi(e) => write!(f, "Gandi error: {e}"),
+            Self::Hetzner(e) => write!(f, "Hetzner error: {e}"),
+            Self::DuckDns(e) => write!(f, "DuckDNS error: {e}"),
+            Self::Namecheap(e) => write!(f, "Namecheap error: {e}"),
+            Self::Rfc2136(e) => write!(f, "RFC 2136 error: {e}"),
+            Self::Http {
+                provider, message, ..
+            } => write!(f, "{provider} error: {message}"),
+            Self::Batch {
+                host_name, message, ..
+            } => write!(f, "{message} (sent in a batch with {host_name})"),
+            Self::AddrParse(e) => write!(f, "Invalid IP address: {e}"),
+            Self::Drift(1) => write!(f, "1 record is out of sync"),
+            Self::Drift(count) => write!(f, "{count} records are out of sync"),
+            Self::SelfTest(1) => write!(f, "1 self-test check failed"),
+            Self::SelfTest(count) => write!(f, "{count} self-test checks failed"),
+            Self::Timeout(timeout) => {
+                write!(f, "Run timed out after {}s", timeout.as_secs())
+            }
+        }
+    }
+}
+
+impl error::Error for DNSUpdateError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Config(_) | Self::Drift(_) | Self::SelfTest(_) | Self::Timeout(_) => None,
+            Self::Detect(e) => Some(e),
+            Self::Lookup(_, e) => Some(e),
+            Self::Route53(e) => Some(e.as_ref()),
+            Self::DigitalOcean(e) => Some(e),
+            Self::GoogleDns(e) => Some(e.as_ref()),
+            Self::Gandi(e) => Some(e),
+            Self::Hetzner(e) => Some(e),
+            Self::DuckDns(e) => Some(e.as_ref()),
+            Self::Namecheap(e) => Some(e.as_ref()),
+            Self::Rfc2136(e) => Some(e.as_ref()),
+            Self::Http { .. } | Self::Batch { .. } => None,
+            Self::AddrParse(e) => Some(e),
+        }
+    }
+}
+
+impl From<aws_sdk_route53::error::BuildError> for DNSUpdateError {
+    fn from(e: aws_sdk_route53::error::BuildError) -> Self {
+        Self::Route53(Box::new(e.into()))
+    }
+}
+
+impl From<net::AddrParseError> for DNSUpdateError {
+    fn from(e: net::AddrParseError) -> Self {
+        Self::AddrParse(e)
+    }
+}
+
+/// Creates the HTTP client, which only connects over the family's protocol
+/// when it's restricted to one.
+fn http_client(
+    timeout: Duration,
+    family: AddressFamily,
+    proxy_url: Option<&str>,
+) -> Result<reqwest::Client, DNSUpdateError> {
+    let local_address = match family {
+        AddressFamily::V4 => Some(IpAddr::from(Ipv4Addr::UNSPECIFIED)),
+        AddressFamily::V6 => Some(IpAddr::from(Ipv6Addr::UNSPECIFIED)),
+        AddressFamily::Any => None,
+    };
+    let mut builder = reqwest::Client::builder();
+    // Without an explicit proxy, reqwest follows HTTPS_PROXY and ALL_PROXY
+    if let Some(proxy_url) = proxy_url {
+        let proxy = reqwest::Proxy::all(proxy_url)
+            .map_err(|e| DNSUpdateError::Config(format!("Invalid proxy URL: {e}")))?;
+        builder = builder.proxy(proxy);
+    }
+    builder
+        .local_address(local_address)
+        .connect_timeout(timeout)
+        .timeout(timeout)
+        .user_agent(USER_AGENT)
+        .build()
+        .map_err(|e| DNSUpdateError::Config(format!("Unable to create HTTP client: {e}")))
+}
+
+/// The updaters of a target, one for each of its providers.
+type Updaters = Vec<(Provider, Box<dyn DNSUpdater>)>;
+
+async fn build_updaters(
+    target: &Target,
+    client: &reqwest::Client,
+) -> Result<Updaters, DNSUpdateError> {
+    let mut updaters = Vec::new();
+    for provider in &target.providers {
+        updaters.push((
+            *provider,
+            build_updater(*provider, &target.settings, client).await?,
+        ));
+    }
+    Ok(updaters)
+}
+
+async fn notify(config: &Config, context: &Context, event: &Event) {
+    if let Some(url) = &config.webhook_url {
+        if config.webhook_on.wants(event) {
+            notify_webhook(&context.client, url, event).await;
+        }
+    }
+    if let Some(topic) = &config.ntfy_topic {
+        notify_ntfy(&context.client, &config.ntfy_server, topic, event).await;
+    }
+}
+
+/// What the hosts of a run share: the state as of its start.
+struct Batch<'a> {
+    state: Option<&'a State>,
+    /// The IP source that detected the address of each record type
+    ip_sources: Vec<(RecordType, String)>,
+}
+
+/// Returns whether the current value of a record is the one given. Addresses
+/// are compared parsed, since IPv6 ones have several spellings, e.g.
+/// 2001:db8::1 and 2001:0db8:0:0:0:0:0:1.
+fn same_value(current: Option<&str>, value: &str) -> bool {
+    match (current.map(str::parse::<IpAddr>), value.parse::<IpAddr>()) {
+        (Some(Ok(current)), Ok(value)) => current == value,
+        _ => current == Some(value),
+    }
+}
+
+/// Returns the providers of the target whose record differs from the one
+/// given, along with the value each currently has.
+async fn stale_providers(
+    config: &Config,
+    context: &Context,
+    batch: &Batch<'_>,
+    target: usize,
+    record: &DnsRecord,
+) -> Result<Vec<(Provider, Option<String>)>, DNSUpdateError> {
+    let host_name = record.name.as_str();
+    let record_type = record.rtype;
+    let record_value = record.value.as_str();
+    match config.compare_source {
+        CompareSource::Resolver => {
+            // Resolvers may still return the old address of a record that was
+            // just set, which would otherwise look stale
+            if batch.state.is_some_and(|state| {
+                state.recently_set(
+                    host_name,
+                    record_type,
+                    record_value,
+                    config.propagation_grace,
+                )
+            }) {
+                debug!(
+                    host_name,
+                    "Record was set to {record_value} recently, waiting for it to propagate"
+                );
+                return Ok(Vec::new());
+            }
+
+            // Only compare against addresses of the record's family, so a
+            // dual-stack host doesn't look stale
+            let family = AddressFamily::for_record_type(record_type);
+            let host_ip = context
+                .resolver
+                .lookup(host_name, family)
+                .await
+                .map_err(|e| DNSUpdateError::Lookup(String::from(host_name), e))?;
+            // A host without addresses of the family, e.g. one with only an
+            // A record when updating AAAA, has the record set rather than
+            // updated
+            debug!(host_name, ?host_ip, "Resolved IP address of host");
+
+            if same_value(host_ip.as_deref(), record_value) {
+                return Ok(Vec::new());
+            }
+            Ok(config.targets[target]
+                .providers
+                .iter()
+                .map(|provider| (*provider, host_ip.clone()))
+                .collect())
+        }
+        CompareSource::Provider => {
+            let updaters = context.updaters(config, target).await?;
+            let mut stale = Vec::new();
+            for (provider, updater) in updaters {
+                let current = updater.current_record(host_name, record_type).await?;
+                debug!(%provider, host_name, ?current, "Read current record from provider");
+                if !same_value(current.as_deref(), record_value) {
+                    stale.push((*provider, current));
+                }
+            }
+            Ok(stale)
+        }
+    }
+}
+
+/// What became of the record of a host.
+enum Outcome {
+    Unchanged,
+    /// Updated at every stale provider
+    Updated,
+    /// Stale at some provider in a dry run
+    WouldUpdate,
+    /// Left stale at some provider to respect MIN_UPDATE_INTERVAL
+    RateLimited,
+    /// Found stale at some provider in check mode
+    Drifted,
+}
+
+/// A record found stale at some providers, waiting to be sent to those that
+/// aren't rate limited.
+struct Pending<'a> {
+    stale: Vec<(Provider, Option<String>)>,
+    updaters: Vec<(Provider, &'a dyn DNSUpdater)>,
+    /// RateLimited if some stale provider was left out
+    outcome: Outcome,
+}
+
+/// What's left to do for the record of a host once it's been compared.
+enum Prepared<'a> {
+    Done(Outcome),
+    Pending(Pending<'a>),
+}
+
+/// The results of sending a record to each provider it was pending at.
+type Sent<'a> = Vec<(Provider, &'a dyn DNSUpdater, Result<(), DNSUpdateError>)>;
+
+/// Compares the record of a host with each provider of the target, returning
+/// the update left to send to the stale ones, if any.
+async fn prepare_update<'a>(
+    config: &Config,
+    context: &'a Context,
+    batch: &Batch<'_>,
+    target: usize,
+    record: &DnsRecord,
+    events: &mut Vec<Event>,
+) -> Result<Prepared<'a>, DNSUpdateError> {
+    let host_name = record.name.as_str();
+    let record_type = record.rtype;
+    let record_value = record.value.as_str();
+    let providers = &config.targets[target].providers;
+    let stale = if config.force_update {
+        providers.iter().map(|provider| (*provider, None)).collect()
+    } else {
+        stale_providers(config, context, batch, target, record).await?
+    };
+    for provider in providers {
+        if !stale.iter().any(|(stale, _)| stale == provider) {
+            events.push(Event::new(
+                host_name,
+                provider,
+                Some(record_value),
+                record_value,
+            ));
+        }
+    }
+    if stale.is_empty() {
+        return Ok(Prepared::Done(Outcome::Unchanged));
+    }
+
+    if config.check {
+        for (provider, current) in &stale {
+            info!(
+                "{} {} record of {} is {}, expected {}",
+                provider,
+                record_type,
+                host_name,
+                current.as_deref().unwrap_or("missing"),
+                record_value
+            );
+        }
+        return Ok(Prepared::Done(Outcome::Drifted));
+    }
+    if config.dry_run {
+        for (provider, current) in &stale {
+            match current {
+                Some(current) => info!(
+                    "Dry run: would update {} {} record of {} from {} to {}",
+                    provider, record_type, host_name, current, record_value
+                ),
+                None => info!(
+                    "Dry run: would set {} {} record of {} to {}",
+                    provider, record_type, host_name, record_value
+                ),
+            }
+            events.push(Event::new(
+                host_name,
+                provider,
+                current.as_deref(),
+                record_value,
+            ));
+        }
+        return Ok(Prepared::Done(Outcome::WouldUpdate));
+    }
+
+    if config.force_update {
+        info!(
+            "Forcing update of {} record of {} to {}",
+            record_type, host_name, record_value
+        );
+    } else {
+        // Providers compared against the resolver all share its value
+        let previous = match stale.as_slice() {
+            [(_, first), rest @ ..] if rest.iter().all(|(_, current)| current == first) => {
+                first.as_deref()
+            }
+            _ => None,
+        };
+        match previous {
+            Some(previous) => info!(
+                "Updating {} record of {} from {} to {}",
+                record_type, host_name, previous, record_value
+            ),
+            None => info!(
+                "Updating {} record of {} to {}",
+                record_type, host_name, record_value
+            ),
+        }
+    }
+    let mut outcome = Outcome::Updated;
+    let mut pending = Vec::new();
+    for (provider, updater) in context.updaters(config, target).await? {
+        if !stale.iter().any(|(stale, _)| stale == provider) {
+            continue;
+        }
+        if !context
+            .rate_limiter
+            .try_acquire(*provider, host_name, record_type)
+        {
+            info!(
+                "Skipping update of {} {} record of {}: updated less than {}s ago",
+                provider,
+                record_type,
+                host_name,
+                config.min_update_interval.as_secs()
+            );
+            outcome = Outcome::RateLimited;
+            continue;
+        }
+        pending.push((*provider, updater.as_ref()));
+    }
+    if pending.is_empty() {
+        return Ok(Prepared::Done(outcome));
+    }
+    Ok(Prepared::Pending(Pending {
+        stale,
+        updaters: pending,
+        outcome,
+    }))
+}
+
+/// Raises the TTL of the record to the lowest the provider accepts.
+pub fn clamp_ttl(provider: Provider, updater: &dyn DNSUpdater, mut record: DnsRecord) -> DnsRecord {
+    let min_ttl = updater.min_ttl();
+    if record.ttl < min_ttl {
+        warn!(
+            "{provider} requires a TTL of at least {min_ttl}, using {min_ttl} instead of {}",
+            record.ttl
+        );
+        record.ttl = min_ttl;
+    }
+    record
+}
+
+/// Sends the pending records of a target's hosts to every provider at once, so
+/// that a slow or failing provider neither delays nor cancels the others.
+/// Returns what was sent for each record, in the order given.
+async fn send_pending<'a>(
+    config: &Config,
+    prepared: &[(DnsRecord, Result<Prepared<'a>, DNSUpdateError>)],
+) -> Vec<Sent<'a>> {
+    // The indices of the records pending at each provider
+    let mut pending_at: Vec<(Provider, &dyn DNSUpdater, Vec<usize>)> = Vec::new();
+    for (index, (_, prepared)) in prepared.iter().enumerate() {
+        let Ok(Prepared::Pending(pending)) = prepared else {
+            continue;
+        };
+        for (provider, updater) in &pending.updaters {
+            match pending_at.iter_mut().find(|(at, _, _)| at == provider) {
+                Some((_, _, indices)) => indices.push(index),
+                None => pending_at.push((*provider, *updater, vec![index])),
+            }
+        }
+    }
+    let batches = pending_at
+        .iter()
+        .map(|(provider, updater, indices)| {
+            let records = indices
+                .iter()
+                .map(|index| clamp_ttl(*provider, *updater, prepared[*index].0.clone()))
+                .collect();
+            (*provider, *updater, records)
+        })
+        .collect();
+    let results = update_concurrently(&config.backoff, batches).await;
+
+    let mut sent: Vec<Sent> = prepared.iter().map(|_| Vec::new()).collect();
+    for ((provider, updater, indices), results) in pending_at.into_iter().zip(results) {
+        for (index, result) in indices.into_iter().zip(results) {
+            sent[index].push((provider, updater, result));
+        }
+    }
+    sent
+}
+
+/// Sends each provider its records at once, so that a slow or failing provider
+/// neither delays nor cancels the others. Returns the results of each
+/// provider's records, in the order given.
+pub async fn update_concurrently(
+    backoff: &Backoff,
+    batches: Vec<(Provider, &dyn DNSUpdater, Vec<DnsRecord>)>,
+) -> Vec<Vec<Result<(), DNSUpdateError>>> {
+    join_all(
+        batches
+            .into_iter()
+            .map(|(provider, updater, records)| send_records(backoff, provider, updater, records)),
+    )
+    .await
+}
+
+/// Sends the records to the provider, all in a single request if it can take
+/// several at once, which is cheaper and leaves no record half updated. The
+/// results are in the order of the records.
+async fn send_records(
+    backoff: &Backoff,
+    provider: Provider,
+    updater: &dyn DNSUpdater,
+    records: Vec<DnsRecord>,
+) -> Vec<Result<(), DNSUpdateError>> {
+    if records.len() > 1 && updater.can_batch() {
+        let span = info_span!("update", %provider, records = records.len());
+        let result = backoff
+            .retry(|| updater.update_batch(records.clone()))
+            .instrument(span)
+            .await;
+        return match result {
+            Ok(()) => records.iter().map(|_| Ok(())).collect(),
+            Err(e) => {
+                // The other records fail along with the first
+                let message = e.to_string();
+                let retryable = e.is_retryable();
+                let mut results = vec![Err(e)];
+                results.extend(records[1..].iter().map(|_| {
+                    Err(DNSUpdateError::Batch {
+                        host_name: records[0].name.clone(),
+                        message: message.clone(),
+                        retryable,
+                    })
+                }));
+                results
+            }
+        };
+    }
+
+    let mut results = Vec::new();
+    for record in records {
+        let span = info_span!("update", %provider, host_name = record.name);
+        let result = backoff
+            .retry(|| updater.update(record.clone()))
+            .instrument(span)
+            .await;
+        results.push(result);
+    }
+    results
+}
+
+/// Records what became of the pending update of a host at each provider.
+async fn finish_update(
+    config: &Config,
+    context: &Context,
+    batch: &Batch<'_>,
+    record: &DnsRecord,
+    pending: Pending<'_>,
+    sent: Sent<'_>,
+    events: &mut Vec<Event>,
+) -> Result<Outcome, DNSUpdateError> {
+    let host_name = record.name.as_str();
+    let record_type = record.rtype;
+    let record_value = record.value.as_str();
+    let mut succeeded = Vec::new();
+    let mut failure = None;
+    for (provider, updater, result) in sent {
+        match &result {
+            Ok(()) => context.metrics.record_update(),
+            Err(_) => context.metrics.record_update_error(provider),
+        }
+        let current = pending
+            .stale
+            .iter()
+            .find(|(stale, _)| *stale == provider)
+            .and_then(|(_, current)| current.as_deref());
+        let mut event = Event::new(host_name, provider, current, record_value);
+        event.error = result.as_ref().err().map(ToString::to_string);
+        events.push(event);
+
+        match result {
+            Ok(()) => {
+                succeeded.push(provider.to_string());
+                if config.audit_txt {
+                    update_audit_txt(config, batch, provider, updater, host_name, record_type)
+                        .await;
+                }
+            }
+            Err(e) => {
+                warn!("Unable to update {provider} {record_type} record of {host_name}: {e}");
+                failure.get_or_insert(e);
+            }
+        }
+    }
+    match failure {
+        Some(e) => {
+            if !succeeded.is_empty() {
+                info!(
+                    "Updated {} record of {} at {} only",
+                    record_type,
+                    host_name,
+                    succeeded.join(", ")
+                );
+            }
+            Err(e)
+        }
+        None => Ok(pending.outcome),
+    }
+}
+
+/// Sets the _dyndns TXT record of the host to the time of the update and the
+/// IP source used. Failures are only logged, since the address record itself
+/// is up to date.
+async fn update_audit_txt(
+    config: &Config,
+    batch: &Batch<'_>,
+    provider: Provider,
+    updater: &dyn DNSUpdater,
+    host_name: &str,
+    record_type: RecordType,
+) {
+    // TXT and CNAME records carry a configured value rather than a detected
+    // address
+    let Some((_, ip_source)) = batch
+        .ip_sources
+        .iter()
+        .find(|(detected, _)| *detected == record_type)
+    else {
+        return;
+    };
+    let updated_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let audit_host_name = format!("_dyndns.{host_name}");
+    let value = format!("updated_at={updated_at} ip_source={ip_source}");
+    let record = DnsRecord::new(&audit_host_name, RecordType::Txt, &value, config.ttl);
+    match updater.update(clamp_ttl(provider, updater, record)).await {
+        Ok(()) => debug!(%provider, host_name = audit_host_name, "Updated audit record"),
+        Err(e) => warn!("Unable to update {provider} audit record {audit_host_name}: {e}"),
+    }
+}
+
+/// Clients and counters shared by every run.
+struct Context {
+    client: reqwest::Client,
+    resolver: Box<dyn Resolver>,
+    /// The source of the address for each record type, or a single source
+    /// when the type follows the address
+    sources: Vec<(Option<RecordType>, Box<dyn IpSource>)>,
+    metrics: Arc<Metrics>,
+    rate_limiter: RateLimiter,
+    /// The updaters of each target, built when first needed, then reused by
+    /// every run so that clients and their credentials are only set up once
+    updaters: Vec<OnceCell<Updaters>>,
+}
+
+impl Context {
+    async fn updaters(
+        &self,
+        config: &Config,
+        target: usize,
+    ) -> Result<&[(Provider, Box<dyn DNSUpdater>)], DNSUpdateError> {
+        let updaters = self.updaters[target]
+            .get_or_try_init(|| build_updaters(&config.targets[target], &self.client))
+            .await?;
+        Ok(updaters)
+    }
+}
+
+/// Returns whether the address was pushed to the host at each of the target's
+/// providers, so that there's nothing to compare.
+fn pushed_to_host(state: &State, target: &Target, host_name: &str, ip: IpAddr) -> bool {
+    target
+        .providers
+        .iter()
+        .all(|provider| state.was_pushed(host_name, *provider, ip))
+}
+
+async fn run_once(
+    config: &Config,
+    context: &Context,
+    summary: &mut RunSummary,
+) -> Result<(), DNSUpdateError> {
+    // Checks compare every record, however recently it was set
+    let mut state = if config.check {
+        None
+    } else {
+        load_state(&config.state_file)
+    };
+    // Whether the state changed in ways besides the updates
+    let mut state_changed = false;
+    let mut records = Vec::new();
+    let mut external_ips = Vec::new();
+    let mut ip_sources = Vec::new();
+    // Addresses unchanged since they were pushed, which only some targets need
+    let mut unchanged_ips = Vec::new();
+    let mut detect_error = None;
+    for (expected, source) in &context.sources {
+        // TXT and CNAME records carry a configured value rather than the
+        // external IP address, so there's nothing to detect
+        if let (Some(record_type), Some(record_value)) = (expected, &config.record_value) {
+            if !record_type.is_address() {
+                records.push((*record_type, record_value.clone()));
+                continue;
+            }
+        }
+
+        let (external_ip, ip_source) = match source.detect_from().await {
+            Ok(detected) => detected,
+            // A dual-stack config still updates the family that's available
+            Err(e) if context.sources.len() > 1 => {
+                let expected = expected.map_or("", RecordType::as_str);
+                warn!("Skipping {expected} records: unable to get current IP address: {e}");
+                detect_error = Some(e);
+                continue;
+            }
+            Err(e) => return Err(DNSUpdateError::Detect(e)),
+        };
+        debug!(%external_ip, "Detected current external IP address");
+        summary.external_ips.push(external_ip);
+        let record_type = RecordType::for_address(external_ip);
+        if let Some(expected) = expected.filter(|expected| *expected != record_type) {
+            return Err(DNSUpdateError::Config(format!(
+                "External IP address {external_ip} can't be stored in {expected} records"
+            )));
+        }
+
+        if let Some(state) = &mut state {
+            if !state.ips.contains(&external_ip) {
+                // Changes of a flapping address are held back until it settles
+                if !config.debounce.is_zero() && !config.force_update {
+                    let stable_for = state.observe(external_ip);
+                    state_changed = true;
+                    if stable_for < config.debounce {
+                        info!(
+                            "External IP address {external_ip} changed, waiting until it's been stable for {}s",
+                            config.debounce.as_secs()
+                        );
+                        external_ips.extend(
+                            state
+                                .ips
+                                .iter()
+                                .filter(|ip| ip.is_ipv4() == external_ip.is_ipv4()),
+                        );
+                        continue;
+                    }
+                }
+                context.metrics.record_ip_change();
+            } else if !config.force_update {
+                state_changed |= state.forget_pending(external_ip);
+                if config.quiet {
+                    debug!("External IP address {external_ip} unchanged since last update");
+                } else {
+                    info!("External IP address {external_ip} unchanged since last update");
+                }
+                let record_value = external_ip.to_string();
+                let mut unpushed = false;
+                for target in config
+                    .targets
+                    .iter()
+                    .filter(|target| target.has_record_type(record_type))
+                {
+                    for host_name in &target.host_names {
+                        // Hosts and providers added since the address was
+                        // pushed still need it
+                        if !pushed_to_host(state, target, host_name, external_ip) {
+                            unpushed = true;
+                            continue;
+                        }
+                        summary.hosts.push(HostResult {
+                            host_name: host_name.clone(),
+                            record_type,
+                            status: HostStatus::Unchanged,
+                        });
+                        for provider in &target.providers {
+                            let event =
+                                Event::new(host_name, provider, Some(&record_value), &record_value);
+                            summary.records.push(RecordSummary::from(&event));
+                        }
+                    }
+                }
+                if !unpushed {
+                    external_ips.push(external_ip);
+                    continue;
+                }
+                unchanged_ips.push(external_ip);
+            }
+        }
+        external_ips.push(external_ip);
+        ip_sources.push((record_type, ip_source));
+        records.push((record_type, external_ip.to_string()));
+    }
+    if let Some(e) = detect_error.filter(|_| external_ips.is_empty()) {
+        return Err(DNSUpdateError::Detect(e));
+    }
+
+    let batch = Batch {
+        state: state.as_ref(),
+        ip_sources,
+    };
+    let mut events = Vec::new();
+    let mut results = Vec::new();
+    let mut set_records = Vec::new();
+    for (index, target) in config.targets.iter().enumerate() {
+        // Every record of the target is compared before any is sent, so that
+        // providers can take them all in one request
+        let mut host_names = Vec::new();
+        let mut prepared = Vec::new();
+        for host_name in &target.host_names {
+            for (record_type, record_value) in &records {
+                if !target.has_record_type(*record_type) {
+                    continue;
+                }
+                // Hosts the unchanged address was pushed to are already
+                // reported as unchanged
+                let unchanged_ip = unchanged_ips
+                    .iter()
+                    .find(|ip| RecordType::for_address(**ip) == *record_type);
+                if let (Some(ip), Some(state)) = (unchanged_ip, &state) {
+                    if pushed_to_host(state, target, host_name, *ip) {
+                        continue;
+                    }
+                }
+                let record = DnsRecord::new(host_name, *record_type, record_value, config.ttl);
+                let result =
+                    prepare_update(config, context, &batch, index, &record, &mut events).await;
+                host_names.push(host_name);
+                prepared.push((record, result));
+            }
+        }
+        let sent = send_pending(config, &prepared).await;
+        for ((host_name, (record, prepared)), sent) in
+            host_names.into_iter().zip(prepared).zip(sent)
+        {
+            let result = match prepared {
+                Ok(Prepared::Done(outcome)) => Ok(outcome),
+                Ok(Prepared::Pending(pending)) => {
+                    finish_update(config, context, &batch, &record, pending, sent, &mut events)
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+            if matches!(result, Ok(Outcome::Updated)) {
+                set_records.push(SetRecord::new(host_name, record.rtype, &record.value));
+            }
+            results.push((target, host_name, record.rtype, result));
+        }
+    }
+
+    for (_, host_name, record_type, result) in &results {
+        let status = match result {
+            Ok(Outcome::Updated) => HostStatus::Updated,
+            Ok(Outcome::WouldUpdate) => HostStatus::WouldUpdate,
+            Ok(Outcome::Unchanged) => HostStatus::Unchanged,
+            Ok(Outcome::RateLimited) => HostStatus::RateLimited,
+            Ok(Outcome::Drifted) => HostStatus::Drifted,
+            Err(e) => HostStatus::Failed(e.to_string()),
+        };
+        summary.hosts.push(HostResult {
+            host_name: String::clone(host_name),
+            record_type: *record_type,
+            status,
+        });
+
+        let record_type = record_type.as_str();
+        match result {
+            Ok(Outcome::Updated) => info!(host_name, record_type, "Updated"),
+            Ok(Outcome::WouldUpdate) => info!(host_name, record_type, "Would update"),
+            Ok(Outcome::Unchanged) if config.quiet => debug!(host_name, record_type, "Unchanged"),
+            Ok(Outcome::Unchanged) => info!(host_name, record_type, "Unchanged"),
+            Ok(Outcome::RateLimited) => info!(host_name, record_type, "Rate limited"),
+            Ok(Outcome::Drifted) => warn!(host_name, record_type, "Out of sync"),
+            Err(e) => error!(host_name, record_type, "Failed: {e}"),
+        }
+    }
+
+    for event in &events {
+        if !config.dry_run {
+            notify(config, context, event).await;
+        }
+        summary.records.push(RecordSummary::from(event));
+    }
+    // Hosts that failed before reaching the providers have no events
+    for (target, host_name, _, result) in &results {
+        if let Err(e) = result {
+            for provider in &target.providers {
+                if !events.iter().any(|event| {
+                    event.host_name == **host_name && event.provider == provider.to_string()
+                }) {
+                    summary
+                        .records
+                        .push(RecordSummary::failed(host_name, provider, e));
+                }
+            }
+        }
+    }
+
+    // Records left stale must be compared again on the next run
+    let rate_limited = results
+        .iter()
+        .any(|(_, _, _, result)| matches!(result, Ok(Outcome::RateLimited)));
+    let drifted = results
+        .iter()
+        .filter(|(_, _, _, result)| matches!(result, Ok(Outcome::Drifted)))
+        .count();
+    let mut pushed_records = Vec::new();
+    for (target, host_name, record_type, result) in &results {
+        if !record_type.is_address() || !matches!(result, Ok(Outcome::Updated | Outcome::Unchanged))
+        {
+            continue;
+        }
+        if let Some((_, value)) = records.iter().find(|(rtype, _)| rtype == record_type) {
+            for provider in &target.providers {
+                pushed_records.push(PushedRecord::new(host_name, *provider, *record_type, value));
+            }
+        }
+    }
+    let error = results
+        .into_iter()
+        .find_map(|(_, _, _, result)| result.err());
+    let pushed = error.is_none() && !rate_limited && !external_ips.is_empty();
+    if !config.dry_run && (pushed || state_changed || !set_records.is_empty()) {
+        let mut state = state.unwrap_or_default();
+        if pushed {
+            state.set_ips(external_ips, pushed_records);
+        }
+        state.remember(set_records, config.propagation_grace);
+        if let Err(e) = save_state(&config.state_file, &state) {
+            warn!(
+                "Unable to save state file {}: {e}",
+                config.state_file.display()
+            );
+        }
+    }
+    match error {
+        Some(e) => Err(e),
+        None if drifted > 0 => Err(DNSUpdateError::Drift(drifted)),
+        None => Ok(()),
+    }
+}
+
+/// Runs once, giving up after RUN_TIMEOUT, then reports the outcome to the
+/// healthcheck if there is one and prints the summary, unless QUIET and
+/// nothing changed.
+async fn run_checked(config: &Config, context: &Context) -> Result<(), DNSUpdateError> {
+    let mut summary = RunSummary::default();
+    config.backoff.reset_budget();
+    let result = if config.run_timeout.is_zero() {
+        run_once(config, context, &mut summary).await
+    } else {
+        tokio::time::timeout(config.run_timeout, run_once(config, context, &mut summary))
+            .await
+            .unwrap_or(Err(DNSUpdateError::Timeout(config.run_timeout)))
+    };
+    if result.is_ok() {
+        context.metrics.record_success();
+    }
+    if let Some(url) = &config.healthcheck_url {
+        ping_healthcheck(&context.client, url, result.is_ok()).await;
+    }
+    if config.quiet && result.is_ok() && summary.unchanged() {
+        return result;
+    }
+    match config.output {
+        Output::Json => {
+            summary.error = result.as_ref().err().map(ToString::to_string);
+            match serde_json::to_string(&summary) {
+                Ok(json) => println!("{json}"),
+                Err(e) => warn!("Unable to serialize run summary: {e}"),
+            }
+        }
+        // The daemon logs each run instead
+        Output::Text if config.daemon_interval.is_none() && !summary.hosts.is_empty() => {
+            print!("{}", summary.host_table());
+        }
+        Output::Text => {}
+    }
+    result
+}
+
+/// Returns a flag that turns true once the process receives SIGTERM or
+/// SIGINT. The handlers are installed before returning, so no signal is missed.
+fn shutdown_flag() -> watch::Receiver<bool> {
+    let (sender, receiver) = watch::channel(false);
+    let terminate = signal(SignalKind::terminate())
+        .map_err(|e| warn!("Unable to listen for SIGTERM: {e}"))
+        .ok();
+    let interrupt = signal(SignalKind::interrupt())
+        .map_err(|e| warn!("Unable to listen for SIGINT: {e}"))
+        .ok();
+    tokio::spawn(async move {
+        let received = |signal: Option<Signal>| async move {
+            match signal {
+                Some(mut signal) => {
+                    signal.recv().await;
+                }
+                None => future::pending().await,
+            }
+        };
+        tokio::select! {
+            () = received(terminate) => info!("Received SIGTERM"),
+            () = received(interrupt) => info!("Received SIGINT"),
+        }
+        let _ = sender.send(true);
+    });
+    receiver
+}
+
+/// Completes once the flag turns true.
+async fn shut_down(mut flag: watch::Receiver<bool>) {
+    let _ = flag.wait_for(|shutdown| *shutdown).await;
+}
+
+/// Sets up the clients and IP sources the configuration calls for.
+fn build_context(config: &Config) -> Result<Context, DNSUpdateError> {
+    let client = http_client(
+        config.http_timeout,
+        AddressFamily::Any,
+        config.proxy_url.as_deref(),
+    )?;
+    let record_types = if config.record_types.is_empty() {
+        vec![None]
+    } else {
+        config.record_types.iter().copied().map(Some).collect()
+    };
+    let mut sources = Vec::new();
+    for record_type in record_types {
+        let family = record_type.map_or(AddressFamily::Any, AddressFamily::for_record_type);
+        let mut source: Box<dyn IpSource> = match config.external_ip {
+            Some(external_ip) => Box::new(FixedSource::new(external_ip, family)),
+            None => Box::new(ip_source(
+                &config.ip_sources,
+                &http_client(config.http_timeout, family, config.proxy_url.as_deref())?,
+                &config.ip_source_headers,
+                family,
+                config.http_timeout,
+            )?),
+        };
+        if let Some(suffix) = config.ipv6_suffix {
+            source = Box::new(SuffixSource::new(source, config.ipv6_prefix_length, suffix));
+        }
+        sources.push((record_type, source));
+    }
+    Ok(Context {
+        client,
+        resolver: resolver(config.resolver),
+        sources,
+        metrics: Arc::default(),
+        rate_limiter: RateLimiter::new(config.min_update_interval),
+        updaters: config.targets.iter().map(|_| OnceCell::new()).collect(),
+    })
+}
+
+/// Runs the program as configured by the arguments, the environment and the
+/// config file: once, or until signalled in daemon mode.
+pub async fn run(args: Args) -> Result<(), DNSUpdateError> {
+    if args.list_providers == Some(true) {
+        for provider in Provider::ALL {
+            println!("{:12}  {provider}", provider.as_str());
+        }
+        return Ok(());
+    }
+    if args.selftest == Some(true) {
+        let checks = selftest(args).await;
+        print!("{}", checklist(&checks));
+        return match failures(&checks) {
+            0 => Ok(()),
+            failed => Err(DNSUpdateError::SelfTest(failed)),
+        };
+    }
+    let config = Config::load(args)?;
+    let context = build_context(&config)?;
+
+    match config.daemon_interval {
+        Some(interval) => {
+            let shutdown = shutdown_flag();
+            let server = config.metrics_port.map(|port| {
+                tokio::spawn(serve_metrics(
+                    Arc::clone(&context.metrics),
+                    port,
+                    shut_down(shutdown.clone()),
+                ))
+            });
+            let mut rng = fastrand::Rng::new();
+            // Signals only take effect between runs, so an update in progress
+            // is never cut short
+            loop {
+                if let Err(e) = run_checked(&config, &context).await {
+                    error!("{e}");
+                }
+                let delay = jittered(interval, config.daemon_jitter, &mut rng);
+                tokio::select! {
+                    () = tokio::time::sleep(delay) => {}
+                    () = shut_down(shutdown.clone()) => break,
+                }
+            }
+            if let Some(server) = server {
+                let _ = server.await;
+            }
+            info!("Shut down cleanly");
+            Ok(())
+        }
+        None => run_checked(&config, &context).await,
+    }
+}
+
+/// Adds a random delay of up to the jitter to the daemon interval.
+pub fn jittered(interval: Duration, jitter: Duration, rng: &mut fastrand::Rng) -> Duration {
+    let max = u64::try_from(jitter.as_millis()).unwrap_or(u64::MAX);
+    interval + Duration::from_millis(rng.u64(0..=max))
+}
@@ -0,0 +1,142 @@
+use crate::providers::Provider;
+use axum::extract::State;
+use axum::http::header;
+use axum::routing::get;
+use axum::Router;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::future::Future;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{error, info};
+
+/// Counters about the update runs, exposed in the Prometheus text format.
+#[derive(Default)]
+pub struct Metrics {
+    updates: AtomicU64,
+    update_errors: Mutex<BTreeMap<String, u64>>,
+    ip_changes: AtomicU64,
+    last_success: AtomicU64,
+}
+
+fn describe(output: &mut String, name: &str, kind: &str, help: &str) {
+    writeln!(output, "# HELP {name} {help}").unwrap();
+    writeln!(output, "# TYPE {name} {kind}").unwrap();
+}
+
+impl Metrics {
+    pub fn record_update(&self) {
+        self.updates.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_update_error(&self, provider: Provider) {
+        let mut update_errors = self.update_errors.lock().unwrap();
+        *update_errors.entry(provider.to_string()).or_default() += 1;
+    }
+
+    pub fn record_ip_change(&self) {
+        self.ip_changes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_success(&self) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        self.last_success.store(now, Ordering::Relaxed);
+    }
+
+    fn render(&self) -> String {
+        let mut output = String::new();
+        describe(
+            &mut output,
+            "dyndns_updates_total",
+            "counter",
+            "Number of records updated.",
+        );
+        writeln!(
+            output,
+            "dyndns_updates_total {}",
+            self.updates.load(Ordering::Relaxed)
+        )
+        .unwrap();
+
+        describe(
+            &mut output,
+            "dyndns_update_errors_total",
+            "counter",
+            "Number of failed record updates.",
+        );
+        for (provider, count) in self.update_errors.lock().unwrap().iter() {
+            writeln!(
+                output,
+                "dyndns_update_errors_total{{provider=\"{provider}\"}} {count}"
+            )
+            .unwrap();
+        }
+
+        describe(
+            &mut output,
+            "dyndns_ip_changes_total",
+            "counter",
+            "Number of times the external IP address changed.",
+        );
+        writeln!(
+            output,
+            "dyndns_ip_changes_total {}",
+            self.ip_changes.load(Ordering::Relaxed)
+        )
+        .unwrap();
+
+        describe(
+            &mut output,
+            "dyndns_last_success_timestamp",
+            "gauge",
+            "Time of the last successful run, in seconds since the Unix epoch.",
+        );
+        writeln!(
+            output,
+            "dyndns_last_success_timestamp {}",
+            self.last_success.load(Ordering::Relaxed)
+        )
+        .unwrap();
+        output
+    }
+}
+
+async fn metrics_handler(
+    State(metrics): State<Arc<Metrics>>,
+) -> ([(header::HeaderName, &'static str); 1], String) {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics.render(),
+    )
+}
+
+/// Serves the metrics at /metrics on the port until the shutdown future
+/// completes.
+pub async fn serve_metrics(
+    metrics: Arc<Metrics>,
+    port: u16,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) {
+    let app = Router::new()
+        .route("/metrics", get(metrics_handler))
+        .with_state(metrics);
+    let address = SocketAddr::from((Ipv4Addr::UNSPECIFIED, port));
+    let listener = match tokio::net::TcpListener::bind(address).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Unable to serve metrics on {address}: {e}");
+            return;
+        }
+    };
+    info!("Serving metrics on {address}");
+    if let Err(e) = axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown)
+        .await
+    {
+        error!("Metrics server failed: {e}");
+    }
+}
@@ -0,0 +1,47 @@
+use crate::webhook::Event;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+pub const DEFAULT_NTFY_SERVER: &str = "https://ntfy.sh";
+
+/// Notifications are best effort, so they mustn't hold up the run for long.
+const PUBLISH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Publishes a message to the ntfy topic if the event changed a record or
+/// failed. Delivery failures are only logged, since they don't affect the
+/// records themselves.
+pub async fn notify_ntfy(client: &reqwest::Client, server: &str, topic: &str, event: &Event) {
+    let (title, message, tags) = match &event.error {
+        // Errors already name the provider
+        Some(e) => (
+            format!("Unable to update {}", event.host_name),
+            e.clone(),
+            "warning",
+        ),
+        None if event.changed() => (
+            format!("Updated {}", event.host_name),
+            format!(
+                "{}: {} -> {}",
+                event.provider,
+                event.old_ip.as_deref().unwrap_or("none"),
+                event.new_ip
+            ),
+            "globe_with_meridians",
+        ),
+        None => return,
+    };
+    let url = format!("{}/{topic}", server.trim_end_matches('/'));
+    let result = client
+        .post(&url)
+        .timeout(PUBLISH_TIMEOUT)
+        .header("Title", title)
+        .header("Tags", tags)
+        .body(message)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status());
+    match result {
+        Ok(_) => debug!(url, "Published ntfy notification"),
+        Err(e) => warn!("Unable to publish ntfy notification to {url}: {e}"),
+    }
+}
@@ -0,0 +1,108 @@
+use super::{
+    http_error, record_data, record_value, relative_name, DNSUpdater, DnsRecord, Provider,
+    RecordType, SendLogged,
+};
+use crate::DNSUpdateError;
+use async_trait::async_trait;
+use serde::Deserialize;
+
+const API_URL: &str = "https://desec.io/api/v1";
+/// The lowest TTL deSEC accepts, in seconds.
+const MIN_TTL: u32 = 3600;
+
+#[derive(Deserialize)]
+struct RRset {
+    records: Vec<String>,
+}
+
+fn desec_error(e: reqwest::Error) -> DNSUpdateError {
+    http_error(Provider::Desec, e)
+}
+
+pub struct DesecUpdater {
+    client: reqwest::Client,
+    api_url: String,
+    token: String,
+    domain: String,
+}
+
+impl DesecUpdater {
+    pub fn new(client: reqwest::Client, token: String, domain: String) -> Self {
+        Self {
+            client,
+            api_url: String::from(API_URL),
+            token,
+            domain,
+        }
+    }
+
+    /// Sends requests to the API at the URL rather than deSEC's, such as a
+    /// proxy or a test server.
+    pub fn with_api_url(mut self, api_url: &str) -> Self {
+        self.api_url = String::from(api_url.trim_end_matches('/'));
+        self
+    }
+
+    fn rrset_url(
+        &self,
+        host_name: &str,
+        record_type: RecordType,
+    ) -> Result<String, DNSUpdateError> {
+        Ok(format!(
+            "{}/domains/{}/rrsets/{}/{}/",
+            self.api_url,
+            self.domain,
+            relative_name(host_name, &self.domain)?,
+            record_type.as_str()
+        ))
+    }
+}
+
+#[async_trait]
+impl DNSUpdater for DesecUpdater {
+    async fn update(&self, record: DnsRecord) -> Result<(), DNSUpdateError> {
+        self.client
+            .patch(self.rrset_url(&record.name, record.rtype)?)
+            .header("Authorization", format!("Token {}", self.token))
+            .json(&serde_json::json!({
+                "records": [record_data(record.rtype, &record.value)],
+                "ttl": record.ttl,
+            }))
+            .send_logged(&[&self.token])
+            .await
+            .and_then(|response| response.error_for_status())
+            .map(|_| ())
+            .map_err(desec_error)
+    }
+
+    async fn current_record(
+        &self,
+        host_name: &str,
+        record_type: RecordType,
+    ) -> Result<Option<String>, DNSUpdateError> {
+        let response = self
+            .client
+            .get(self.rrset_url(host_name, record_type)?)
+            .header("Authorization", format!("Token {}", self.token))
+            .send_logged(&[&self.token])
+            .await
+            .map_err(desec_error)?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let rrset: RRset = response
+            .error_for_status()
+            .map_err(desec_error)?
+            .json()
+            .await
+            .map_err(desec_error)?;
+        Ok(rrset
+            .records
+            .first()
+            .map(|data| record_value(record_type, data)))
+    }
+
+    fn min_ttl(&self) -> u32 {
+        MIN_TTL
+    }
+}
@@ -0,0 +1,102 @@
+use super::{record_data, record_value, DNSUpdater, DnsRecord, RecordType, SendLogged};
+use crate::DNSUpdateError;
+use async_trait::async_trait;
+use serde::Deserialize;
+
+const API_URL: &str = "https://api.digitalocean.com/v2";
+/// The lowest TTL DigitalOcean accepts, in seconds.
+const MIN_TTL: u32 = 30;
+
+#[derive(Deserialize)]
+struct DomainRecord {
+    data: String,
+}
+
+#[derive(Deserialize)]
+struct DomainRecordResponse {
+    domain_record: DomainRecord,
+}
+
+pub struct DigitalOceanUpdater {
+    client: reqwest::Client,
+    api_url: String,
+    token: String,
+    domain: String,
+    record_id: String,
+}
+
+impl DigitalOceanUpdater {
+    pub fn new(client: reqwest::Client, token: String, domain: String, record_id: String) -> Self {
+        Self {
+            client,
+            api_url: String::from(API_URL),
+            token,
+            domain,
+            record_id,
+        }
+    }
+
+    /// Sends requests to the API at the URL rather than DigitalOcean's, such as a
+    /// proxy or a test server.
+    pub fn with_api_url(mut self, api_url: &str) -> Self {
+        self.api_url = String::from(api_url.trim_end_matches('/'));
+        self
+    }
+}
+
+#[async_trait]
+impl DNSUpdater for DigitalOceanUpdater {
+    async fn update(&self, record: DnsRecord) -> Result<(), DNSUpdateError> {
+        // TXT values are quoted by the API, while CNAME targets have to be
+        // fully qualified
+        let data = match record.rtype {
+            RecordType::Cname => record_data(record.rtype, &record.value),
+            _ => record.value,
+        };
+        self.client
+            .put(format!(
+                "{}/domains/{}/records/{}",
+                self.api_url, self.domain, self.record_id
+            ))
+            .bearer_auth(&self.token)
+            .json(&serde_json::json!({
+                "type": record.rtype.as_str(),
+                "data": data,
+                "ttl": record.ttl,
+            }))
+            .send_logged(&[&self.token])
+            .await
+            .and_then(|response| response.error_for_status())
+            .map(|_| ())
+            .map_err(DNSUpdateError::DigitalOcean)
+    }
+
+    async fn current_record(
+        &self,
+        _host_name: &str,
+        record_type: RecordType,
+    ) -> Result<Option<String>, DNSUpdateError> {
+        let response: DomainRecordResponse = self
+            .client
+            .get(format!(
+                "{}/domains/{}/records/{}",
+                self.api_url, self.domain, self.record_id
+            ))
+            .bearer_auth(&self.token)
+            .send_logged(&[&self.token])
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(DNSUpdateError::DigitalOcean)?
+            .json()
+            .await
+            .map_err(DNSUpdateError::DigitalOcean)?;
+        Ok(Some(record_value(
+            record_type,
+            &response.domain_record.data,
+        )))
+    }
+
+    fn min_ttl(&self) -> u32 {
+        MIN_TTL
+    }
+}
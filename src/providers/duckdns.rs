@@ -0,0 +1,80 @@
+use super::{relative_name, DNSUpdater, DnsRecord, RecordType, SendLogged};
+use crate::DNSUpdateError;
+use async_trait::async_trait;
+
+const API_URL: &str = "https://www.duckdns.org/update";
+
+pub struct DuckDnsUpdater {
+    client: reqwest::Client,
+    api_url: String,
+    token: String,
+}
+
+impl DuckDnsUpdater {
+    pub fn new(client: reqwest::Client, token: String) -> Self {
+        Self {
+            client,
+            api_url: String::from(API_URL),
+            token,
+        }
+    }
+
+    /// Sends requests to the API at the URL rather than DuckDNS's, such as a
+    /// proxy or a test server.
+    pub fn with_api_url(mut self, api_url: &str) -> Self {
+        self.api_url = String::from(api_url.trim_end_matches('/'));
+        self
+    }
+}
+
+#[async_trait]
+impl DNSUpdater for DuckDnsUpdater {
+    async fn update(&self, record: DnsRecord) -> Result<(), DNSUpdateError> {
+        let value_param = match record.rtype {
+            RecordType::A => "ip",
+            RecordType::Aaaa => "ipv6",
+            RecordType::Txt => "txt",
+            RecordType::Cname => {
+                return Err(DNSUpdateError::Config(String::from(
+                    "DuckDNS doesn't support CNAME records",
+                )))
+            }
+        };
+        let subdomain = relative_name(&record.name, "duckdns.org")?;
+        let body = self
+            .client
+            .get(&self.api_url)
+            .query(&[
+                ("domains", subdomain.as_str()),
+                ("token", &self.token),
+                (value_param, &record.value),
+            ])
+            .send_logged(&[&self.token])
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| DNSUpdateError::DuckDns(e.into()))?
+            .text()
+            .await
+            .map_err(|e| DNSUpdateError::DuckDns(e.into()))?;
+        match body.trim() {
+            "OK" => Ok(()),
+            _ => Err(DNSUpdateError::DuckDns(
+                format!("update of {subdomain} rejected with {}", body.trim()).into(),
+            )),
+        }
+    }
+
+    async fn current_record(
+        &self,
+        _host_name: &str,
+        _record_type: RecordType,
+    ) -> Result<Option<String>, DNSUpdateError> {
+        Err(DNSUpdateError::Config(String::from(
+            "DuckDNS records can't be read back; use COMPARE_SOURCE=resolver",
+        )))
+    }
+
+    fn can_read(&self) -> bool {
+        false
+    }
+}
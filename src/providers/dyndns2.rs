@@ -0,0 +1,154 @@
+use super::{http_error, DNSUpdater, DnsRecord, Provider, RecordType, SendLogged};
+use crate::{DNSUpdateError, USER_AGENT};
+use async_trait::async_trait;
+
+/// Names of the query parameters carrying the host name and addresses.
+pub struct Fields {
+    pub host: &'static str,
+    pub ipv4: &'static str,
+    pub ipv6: &'static str,
+}
+
+impl Fields {
+    /// The parameters most services use.
+    pub const STANDARD: Self = Self {
+        host: "hostname",
+        ipv4: "myip",
+        ipv6: "myipv6",
+    };
+}
+
+/// What an update asks of the service.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DynDnsOp {
+    /// Point the host at the address
+    Update,
+    /// Mark the host offline, e.g. when decommissioning it, leaving it to the
+    /// service to decide what the host resolves to
+    Offline,
+}
+
+/// Explains the response codes of failed updates.
+fn describe(code: &str) -> &str {
+    match code {
+        "nohost" => "the host name doesn't exist in the account",
+        "notfqdn" => "the host name isn't fully qualified",
+        "badauth" => "the username or password is wrong",
+        "badagent" => "the client is blocked",
+        "!donator" => "the update needs a paid account",
+        "numhost" => "too many hosts were given",
+        "abuse" => "the host name is blocked for abuse",
+        "dnserr" | "911" => "the service is having problems",
+        _ => "an unknown error",
+    }
+}
+
+/// Checks the response to an update, where successful responses are followed
+/// by the address, e.g. "good 1.2.3.4", and failures are a single code.
+fn parse_response(body: &str) -> Result<(), String> {
+    match body.split_whitespace().next() {
+        Some("good" | "nochg") => Ok(()),
+        Some(code) => Err(format!("rejected with {code}: {}", describe(code))),
+        None => Err(String::from("empty response")),
+    }
+}
+
+/// Updates records with the query string protocol DynDNS introduced and many
+/// dynamic DNS services copied, authenticating with HTTP basic auth.
+pub struct DynDns2Updater {
+    client: reqwest::Client,
+    provider: Provider,
+    url: String,
+    fields: Fields,
+    username: String,
+    password: String,
+    op: DynDnsOp,
+}
+
+impl DynDns2Updater {
+    /// Creates an updater for the provider's service at the URL.
+    pub fn new(
+        client: reqwest::Client,
+        provider: Provider,
+        url: &str,
+        fields: Fields,
+        username: String,
+        password: String,
+        op: DynDnsOp,
+    ) -> Self {
+        Self {
+            client,
+            provider,
+            url: String::from(url),
+            fields,
+            username,
+            password,
+            op,
+        }
+    }
+
+    /// Sends updates to the URL rather than the provider's, such as a proxy
+    /// or a test server.
+    pub fn with_api_url(mut self, url: &str) -> Self {
+        self.url = String::from(url);
+        self
+    }
+}
+
+#[async_trait]
+impl DNSUpdater for DynDns2Updater {
+    async fn update(&self, record: DnsRecord) -> Result<(), DNSUpdateError> {
+        let value_param = match record.rtype {
+            RecordType::A => self.fields.ipv4,
+            RecordType::Aaaa => self.fields.ipv6,
+            RecordType::Txt | RecordType::Cname => {
+                return Err(DNSUpdateError::Config(format!(
+                    "{} dynamic DNS doesn't support {} records",
+                    self.provider, record.rtype
+                )))
+            }
+        };
+        let query = match self.op {
+            DynDnsOp::Update => [
+                (self.fields.host, record.name.as_str()),
+                (value_param, &record.value),
+            ],
+            DynDnsOp::Offline => [(self.fields.host, record.name.as_str()), ("offline", "YES")],
+        };
+        let response = self
+            .client
+            .get(&self.url)
+            .basic_auth(&self.username, Some(&self.password))
+            .header("User-Agent", USER_AGENT)
+            .query(&query)
+            .send_logged(&[&self.password])
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| http_error(self.provider, e))?;
+        let status = response.status().as_u16();
+        let body = response
+            .text()
+            .await
+            .map_err(|e| http_error(self.provider, e))?;
+        parse_response(&body).map_err(|message| DNSUpdateError::Http {
+            provider: self.provider,
+            status: Some(status),
+            message: format!("update of {} {message}", record.name),
+        })
+    }
+
+    async fn current_record(
+        &self,
+        _host_name: &str,
+        _record_type: RecordType,
+    ) -> Result<Option<String>, DNSUpdateError> {
+        Err(DNSUpdateError::Config(format!(
+            "{} records can't be read back; use COMPARE_SOURCE=resolver",
+            self.provider
+        )))
+    }
+
+    fn can_read(&self) -> bool {
+        false
+    }
+}
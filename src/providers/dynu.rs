@@ -0,0 +1,36 @@
+use super::dyndns2::{DynDns2Updater, DynDnsOp, Fields};
+use super::Provider;
+use ring::digest::{digest, SHA256};
+use std::fmt::Write;
+
+const API_URL: &str = "https://api.dynu.com/nic/update";
+
+/// Returns the hex SHA-256 hash of the password, which Dynu accepts in place
+/// of the password itself. Dynu takes an MD5 hash too, but SHA-256 is used
+/// since ring, which is already a dependency, has no MD5.
+fn hash_password(password: &str) -> String {
+    digest(&SHA256, password.as_bytes())
+        .as_ref()
+        .iter()
+        .fold(String::new(), |mut hash, byte| {
+            write!(hash, "{byte:02x}").unwrap();
+            hash
+        })
+}
+
+pub fn dynu_updater(
+    client: reqwest::Client,
+    username: String,
+    password: &str,
+    op: DynDnsOp,
+) -> DynDns2Updater {
+    DynDns2Updater::new(
+        client,
+        Provider::Dynu,
+        API_URL,
+        Fields::STANDARD,
+        username,
+        hash_password(password),
+        op,
+    )
+}
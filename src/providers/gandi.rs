@@ -0,0 +1,97 @@
+use super::{
+    record_data, record_value, relative_name, DNSUpdater, DnsRecord, RecordType, SendLogged,
+};
+use crate::DNSUpdateError;
+use async_trait::async_trait;
+use serde::Deserialize;
+
+const API_URL: &str = "https://api.gandi.net/v5/livedns";
+/// The lowest TTL Gandi accepts, in seconds.
+const MIN_TTL: u32 = 300;
+
+#[derive(Deserialize)]
+struct Rrset {
+    rrset_values: Vec<String>,
+}
+
+pub struct GandiUpdater {
+    client: reqwest::Client,
+    api_url: String,
+    token: String,
+    domain: String,
+}
+
+impl GandiUpdater {
+    pub fn new(client: reqwest::Client, token: String, domain: String) -> Self {
+        Self {
+            client,
+            api_url: String::from(API_URL),
+            token,
+            domain,
+        }
+    }
+
+    /// Sends requests to the API at the URL rather than Gandi's, such as a
+    /// proxy or a test server.
+    pub fn with_api_url(mut self, api_url: &str) -> Self {
+        self.api_url = String::from(api_url.trim_end_matches('/'));
+        self
+    }
+}
+
+#[async_trait]
+impl DNSUpdater for GandiUpdater {
+    async fn update(&self, record: DnsRecord) -> Result<(), DNSUpdateError> {
+        let name = relative_name(&record.name, &self.domain)?;
+        self.client
+            .put(format!(
+                "{}/domains/{}/records/{name}/{}",
+                self.api_url, self.domain, record.rtype
+            ))
+            .bearer_auth(&self.token)
+            .json(&serde_json::json!({
+                "rrset_ttl": record.ttl,
+                "rrset_values": [record_data(record.rtype, &record.value)],
+            }))
+            .send_logged(&[&self.token])
+            .await
+            .and_then(|response| response.error_for_status())
+            .map(|_| ())
+            .map_err(DNSUpdateError::Gandi)
+    }
+
+    async fn current_record(
+        &self,
+        host_name: &str,
+        record_type: RecordType,
+    ) -> Result<Option<String>, DNSUpdateError> {
+        let name = relative_name(host_name, &self.domain)?;
+        let response = self
+            .client
+            .get(format!(
+                "{}/domains/{}/records/{name}/{record_type}",
+                self.api_url, self.domain
+            ))
+            .bearer_auth(&self.token)
+            .send_logged(&[&self.token])
+            .await
+            .map_err(DNSUpdateError::Gandi)?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let rrset: Rrset = response
+            .error_for_status()
+            .map_err(DNSUpdateError::Gandi)?
+            .json()
+            .await
+            .map_err(DNSUpdateError::Gandi)?;
+        Ok(rrset
+            .rrset_values
+            .first()
+            .map(|data| record_value(record_type, data)))
+    }
+
+    fn min_ttl(&self) -> u32 {
+        MIN_TTL
+    }
+}
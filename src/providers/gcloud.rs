@@ -0,0 +1,218 @@
+use super::{record_data, record_value, DNSUpdater, DnsRecord, RecordType, SendLogged};
+use crate::DNSUpdateError;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{error, fs};
+
+const API_URL: &str = "https://dns.googleapis.com/dns/v1";
+const SCOPE: &str = "https://www.googleapis.com/auth/ndev.clouddns.readwrite";
+
+#[derive(Deserialize)]
+struct ServiceAccount {
+    client_email: String,
+    private_key: String,
+    token_uri: String,
+}
+
+#[derive(Serialize)]
+struct Claims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: u64,
+    exp: u64,
+}
+
+#[derive(Deserialize)]
+struct Token {
+    access_token: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct ResourceRecordSet {
+    name: String,
+    #[serde(rename = "type")]
+    record_type: String,
+    ttl: u32,
+    rrdatas: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ResourceRecordSetList {
+    #[serde(default)]
+    rrsets: Vec<ResourceRecordSet>,
+}
+
+#[derive(Debug, Serialize)]
+struct Change {
+    additions: Vec<ResourceRecordSet>,
+    deletions: Vec<ResourceRecordSet>,
+}
+
+/// Builds a change replacing the existing record sets with the given one.
+fn change(existing: Vec<ResourceRecordSet>, addition: ResourceRecordSet) -> Change {
+    Change {
+        additions: vec![addition],
+        deletions: existing,
+    }
+}
+
+/// Returns the fully qualified form of the host name, as Cloud DNS expects.
+fn fully_qualified(host_name: &str) -> String {
+    if host_name.ends_with('.') {
+        String::from(host_name)
+    } else {
+        format!("{host_name}.")
+    }
+}
+
+fn google_dns_error(e: impl Into<Box<dyn error::Error + Send + Sync>>) -> DNSUpdateError {
+    DNSUpdateError::GoogleDns(e.into())
+}
+
+pub struct GoogleDnsUpdater {
+    client: reqwest::Client,
+    api_url: String,
+    project: String,
+    zone: String,
+    credentials_path: String,
+}
+
+impl GoogleDnsUpdater {
+    pub fn new(
+        client: reqwest::Client,
+        project: String,
+        zone: String,
+        credentials_path: String,
+    ) -> Self {
+        Self {
+            client,
+            api_url: String::from(API_URL),
+            project,
+            zone,
+            credentials_path,
+        }
+    }
+
+    /// Sends requests to the API at the URL rather than Cloud DNS's, such as a
+    /// proxy or a test server.
+    pub fn with_api_url(mut self, api_url: &str) -> Self {
+        self.api_url = String::from(api_url.trim_end_matches('/'));
+        self
+    }
+
+    async fn access_token(&self) -> Result<String, DNSUpdateError> {
+        let credentials = fs::read(&self.credentials_path).map_err(google_dns_error)?;
+        let service_account: ServiceAccount =
+            serde_json::from_slice(&credentials).map_err(google_dns_error)?;
+        let iat = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(google_dns_error)?
+            .as_secs();
+        let claims = Claims {
+            iss: &service_account.client_email,
+            scope: SCOPE,
+            aud: &service_account.token_uri,
+            iat,
+            exp: iat + 3600,
+        };
+        let key = jsonwebtoken::EncodingKey::from_rsa_pem(service_account.private_key.as_bytes())
+            .map_err(google_dns_error)?;
+        let assertion = jsonwebtoken::encode(
+            &jsonwebtoken::Header::new(jsonwebtoken::Algorithm::RS256),
+            &claims,
+            &key,
+        )
+        .map_err(google_dns_error)?;
+        // The response carries the access token, so it isn't logged
+        let token: Token = self
+            .client
+            .post(&service_account.token_uri)
+            .form(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                ("assertion", &assertion),
+            ])
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(google_dns_error)?
+            .json()
+            .await
+            .map_err(google_dns_error)?;
+        Ok(token.access_token)
+    }
+
+    async fn existing(
+        &self,
+        access_token: &str,
+        name: &str,
+        record_type: &str,
+    ) -> Result<Vec<ResourceRecordSet>, DNSUpdateError> {
+        let list: ResourceRecordSetList = self
+            .client
+            .get(format!(
+                "{}/projects/{}/managedZones/{}/rrsets",
+                self.api_url, self.project, self.zone
+            ))
+            .bearer_auth(access_token)
+            .query(&[("name", name), ("type", record_type)])
+            .send_logged(&[access_token])
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(google_dns_error)?
+            .json()
+            .await
+            .map_err(google_dns_error)?;
+        Ok(list.rrsets)
+    }
+}
+
+#[async_trait]
+impl DNSUpdater for GoogleDnsUpdater {
+    async fn update(&self, record: DnsRecord) -> Result<(), DNSUpdateError> {
+        let name = fully_qualified(&record.name);
+        let access_token = self.access_token().await?;
+        let existing = self
+            .existing(&access_token, &name, record.rtype.as_str())
+            .await?;
+        let addition = ResourceRecordSet {
+            name,
+            record_type: String::from(record.rtype.as_str()),
+            ttl: record.ttl,
+            rrdatas: vec![record_data(record.rtype, &record.value)],
+        };
+        self.client
+            .post(format!(
+                "{}/projects/{}/managedZones/{}/changes",
+                self.api_url, self.project, self.zone
+            ))
+            .bearer_auth(&access_token)
+            .json(&change(existing, addition))
+            .send_logged(&[&access_token])
+            .await
+            .and_then(|response| response.error_for_status())
+            .map(|_| ())
+            .map_err(google_dns_error)
+    }
+
+    async fn current_record(
+        &self,
+        host_name: &str,
+        record_type: RecordType,
+    ) -> Result<Option<String>, DNSUpdateError> {
+        let access_token = self.access_token().await?;
+        let existing = self
+            .existing(
+                &access_token,
+                &fully_qualified(host_name),
+                record_type.as_str(),
+            )
+            .await?;
+        Ok(existing
+            .iter()
+            .flat_map(|rrset| &rrset.rrdatas)
+            .next()
+            .map(|data| record_value(record_type, data)))
+    }
+}
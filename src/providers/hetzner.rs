@@ -0,0 +1,91 @@
+use super::{record_data, record_value, DNSUpdater, DnsRecord, RecordType, SendLogged};
+use crate::DNSUpdateError;
+use async_trait::async_trait;
+use serde::Deserialize;
+
+const API_URL: &str = "https://dns.hetzner.com/api/v1";
+
+#[derive(Deserialize)]
+struct Record {
+    name: String,
+    value: String,
+}
+
+#[derive(Deserialize)]
+struct RecordResponse {
+    record: Record,
+}
+
+pub struct HetznerUpdater {
+    client: reqwest::Client,
+    api_url: String,
+    token: String,
+    zone_id: String,
+    record_id: String,
+}
+
+impl HetznerUpdater {
+    pub fn new(client: reqwest::Client, token: String, zone_id: String, record_id: String) -> Self {
+        Self {
+            client,
+            api_url: String::from(API_URL),
+            token,
+            zone_id,
+            record_id,
+        }
+    }
+
+    /// Sends requests to the API at the URL rather than Hetzner's, such as a
+    /// proxy or a test server.
+    pub fn with_api_url(mut self, api_url: &str) -> Self {
+        self.api_url = String::from(api_url.trim_end_matches('/'));
+        self
+    }
+
+    async fn record(&self) -> Result<Record, DNSUpdateError> {
+        let response: RecordResponse = self
+            .client
+            .get(format!("{}/records/{}", self.api_url, self.record_id))
+            .header("Auth-API-Token", &self.token)
+            .send_logged(&[&self.token])
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(DNSUpdateError::Hetzner)?
+            .json()
+            .await
+            .map_err(DNSUpdateError::Hetzner)?;
+        Ok(response.record)
+    }
+}
+
+#[async_trait]
+impl DNSUpdater for HetznerUpdater {
+    async fn update(&self, record: DnsRecord) -> Result<(), DNSUpdateError> {
+        // Hetzner requires the record name on every update, so look up the
+        // existing one rather than risk renaming the record
+        let name = self.record().await?.name;
+        self.client
+            .put(format!("{}/records/{}", self.api_url, self.record_id))
+            .header("Auth-API-Token", &self.token)
+            .json(&serde_json::json!({
+                "zone_id": self.zone_id,
+                "type": record.rtype.as_str(),
+                "name": name,
+                "value": record_data(record.rtype, &record.value),
+                "ttl": record.ttl,
+            }))
+            .send_logged(&[&self.token])
+            .await
+            .and_then(|response| response.error_for_status())
+            .map(|_| ())
+            .map_err(DNSUpdateError::Hetzner)
+    }
+
+    async fn current_record(
+        &self,
+        _host_name: &str,
+        record_type: RecordType,
+    ) -> Result<Option<String>, DNSUpdateError> {
+        Ok(Some(record_value(record_type, &self.record().await?.value)))
+    }
+}
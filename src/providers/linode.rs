@@ -0,0 +1,101 @@
+use super::{http_error, DNSUpdater, DnsRecord, Provider, RecordType, SendLogged};
+use crate::DNSUpdateError;
+use async_trait::async_trait;
+use serde::Deserialize;
+
+const API_URL: &str = "https://api.linode.com/v4";
+
+#[derive(Deserialize)]
+struct DomainRecord {
+    target: String,
+}
+
+fn linode_error(e: reqwest::Error) -> DNSUpdateError {
+    if e.status() == Some(reqwest::StatusCode::UNAUTHORIZED) {
+        return DNSUpdateError::Http {
+            provider: Provider::Linode,
+            status: Some(reqwest::StatusCode::UNAUTHORIZED.as_u16()),
+            message: String::from(
+                "authentication failed; check that LINODE_TOKEN is valid and can modify domains",
+            ),
+        };
+    }
+    http_error(Provider::Linode, e)
+}
+
+pub struct LinodeUpdater {
+    client: reqwest::Client,
+    api_url: String,
+    token: String,
+    domain_id: String,
+    record_id: String,
+}
+
+impl LinodeUpdater {
+    pub fn new(
+        client: reqwest::Client,
+        token: String,
+        domain_id: String,
+        record_id: String,
+    ) -> Self {
+        Self {
+            client,
+            api_url: String::from(API_URL),
+            token,
+            domain_id,
+            record_id,
+        }
+    }
+
+    /// Sends requests to the API at the URL rather than Linode's, such as a
+    /// proxy or a test server.
+    pub fn with_api_url(mut self, api_url: &str) -> Self {
+        self.api_url = String::from(api_url.trim_end_matches('/'));
+        self
+    }
+
+    fn record_url(&self) -> String {
+        format!(
+            "{}/domains/{}/records/{}",
+            self.api_url, self.domain_id, self.record_id
+        )
+    }
+}
+
+#[async_trait]
+impl DNSUpdater for LinodeUpdater {
+    async fn update(&self, record: DnsRecord) -> Result<(), DNSUpdateError> {
+        self.client
+            .put(self.record_url())
+            .bearer_auth(&self.token)
+            .json(&serde_json::json!({
+                "type": record.rtype.as_str(),
+                "target": record.value,
+                "ttl_sec": record.ttl,
+            }))
+            .send_logged(&[&self.token])
+            .await
+            .and_then(|response| response.error_for_status())
+            .map(|_| ())
+            .map_err(linode_error)
+    }
+
+    async fn current_record(
+        &self,
+        _host_name: &str,
+        _record_type: RecordType,
+    ) -> Result<Option<String>, DNSUpdateError> {
+        let record: DomainRecord = self
+            .client
+            .get(self.record_url())
+            .bearer_auth(&self.token)
+            .send_logged(&[&self.token])
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(linode_error)?
+            .json()
+            .await
+            .map_err(linode_error)?;
+        Ok(Some(record.target))
+    }
+}
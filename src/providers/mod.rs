@@ -0,0 +1,572 @@
+use crate::config::Settings;
+use crate::DNSUpdateError;
+use async_trait::async_trait;
+use reqwest::ResponseBuilderExt;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::{fmt, str};
+use tracing::{debug, Level};
+
+mod desec;
+mod digitalocean;
+mod duckdns;
+mod dyndns2;
+mod dynu;
+mod gandi;
+mod gcloud;
+mod hetzner;
+mod linode;
+mod namecheap;
+mod noip;
+mod ovh;
+mod porkbun;
+mod rfc2136;
+mod route53;
+mod vultr;
+
+pub use desec::DesecUpdater;
+pub use digitalocean::DigitalOceanUpdater;
+pub use duckdns::DuckDnsUpdater;
+pub use dyndns2::{DynDns2Updater, DynDnsOp, Fields};
+pub use dynu::dynu_updater;
+pub use gandi::GandiUpdater;
+pub use gcloud::GoogleDnsUpdater;
+pub use hetzner::HetznerUpdater;
+pub use linode::LinodeUpdater;
+pub use namecheap::NamecheapUpdater;
+pub use noip::noip_updater;
+pub use ovh::{ovh_signature, OvhUpdater};
+pub use porkbun::PorkbunUpdater;
+pub use rfc2136::Rfc2136Updater;
+pub use route53::{
+    hosted_zone_id_by_name, reverse_name, route53_client, Route53Updater, RoutingPolicy,
+};
+pub use vultr::VultrUpdater;
+
+/// A record to set at a provider, which each updater translates into the
+/// types of its own API.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DnsRecord {
+    pub name: String,
+    pub rtype: RecordType,
+    /// The address, or the unquoted text of a TXT record
+    pub value: String,
+    pub ttl: u32,
+}
+
+impl DnsRecord {
+    pub fn new(name: &str, rtype: RecordType, value: &str, ttl: u32) -> Self {
+        Self {
+            name: String::from(name),
+            rtype,
+            value: String::from(value),
+            ttl,
+        }
+    }
+}
+
+#[async_trait]
+pub trait DNSUpdater: Send + Sync {
+    async fn update(&self, record: DnsRecord) -> Result<(), DNSUpdateError>;
+
+    /// Reads the value the provider currently serves for the record, if any.
+    async fn current_record(
+        &self,
+        host_name: &str,
+        record_type: RecordType,
+    ) -> Result<Option<String>, DNSUpdateError>;
+
+    /// Returns the lowest TTL the provider accepts, in seconds.
+    fn min_ttl(&self) -> u32 {
+        0
+    }
+
+    /// Returns whether current_record can read records back, which
+    /// update-only APIs can't.
+    fn can_read(&self) -> bool {
+        true
+    }
+
+    /// Updates the records in a single request where can_batch says the API
+    /// allows it, or else one at a time.
+    async fn update_batch(&self, records: Vec<DnsRecord>) -> Result<(), DNSUpdateError> {
+        for record in records {
+            self.update(record).await?;
+        }
+        Ok(())
+    }
+
+    /// Returns whether update_batch sends every record in a single request.
+    fn can_batch(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum RecordType {
+    A,
+    Aaaa,
+    Txt,
+    Cname,
+}
+
+impl RecordType {
+    /// Returns the address record type matching the IP address.
+    pub fn for_address(ip: IpAddr) -> Self {
+        match ip {
+            IpAddr::V4(_) => Self::A,
+            IpAddr::V6(_) => Self::Aaaa,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::A => "A",
+            Self::Aaaa => "AAAA",
+            Self::Txt => "TXT",
+            Self::Cname => "CNAME",
+        }
+    }
+
+    /// Returns whether records of the type hold an IP address, rather than a
+    /// configured value.
+    pub fn is_address(self) -> bool {
+        matches!(self, Self::A | Self::Aaaa)
+    }
+}
+
+impl str::FromStr for RecordType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "A" => Ok(Self::A),
+            "AAAA" => Ok(Self::Aaaa),
+            "TXT" => Ok(Self::Txt),
+            "CNAME" => Ok(Self::Cname),
+            _ => Err(format!("Unsupported record type {s}")),
+        }
+    }
+}
+
+impl fmt::Display for RecordType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Provider {
+    Route53,
+    DigitalOcean,
+    GoogleDns,
+    Gandi,
+    Hetzner,
+    DuckDns,
+    Namecheap,
+    Rfc2136,
+    Linode,
+    Vultr,
+    Ovh,
+    Porkbun,
+    Desec,
+    Dynu,
+    NoIp,
+}
+
+impl Provider {
+    pub const ALL: [Self; 15] = [
+        Self::Route53,
+        Self::DigitalOcean,
+        Self::GoogleDns,
+        Self::Gandi,
+        Self::Hetzner,
+        Self::DuckDns,
+        Self::Namecheap,
+        Self::Rfc2136,
+        Self::Linode,
+        Self::Vultr,
+        Self::Ovh,
+        Self::Porkbun,
+        Self::Desec,
+        Self::Dynu,
+        Self::NoIp,
+    ];
+
+    /// Returns the name of the provider in the PROVIDER setting.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Route53 => "route53",
+            Self::DigitalOcean => "digitalocean",
+            Self::GoogleDns => "gcloud",
+            Self::Gandi => "gandi",
+            Self::Hetzner => "hetzner",
+            Self::DuckDns => "duckdns",
+            Self::Namecheap => "namecheap",
+            Self::Rfc2136 => "rfc2136",
+            Self::Linode => "linode",
+            Self::Vultr => "vultr",
+            Self::Ovh => "ovh",
+            Self::Porkbun => "porkbun",
+            Self::Desec => "desec",
+            Self::Dynu => "dynu",
+            Self::NoIp => "noip",
+        }
+    }
+
+    /// Returns the setting of the record ID for providers that update a single
+    /// record by its ID, whatever the name and type of the record being set.
+    pub fn record_id_setting(self) -> Option<&'static str> {
+        match self {
+            Self::DigitalOcean => Some("DIGITALOCEAN_RECORD_ID"),
+            Self::Hetzner => Some("HETZNER_RECORD_ID"),
+            Self::Linode => Some("LINODE_RECORD_ID"),
+            Self::Vultr => Some("VULTR_RECORD_ID"),
+            Self::Ovh => Some("OVH_RECORD_ID"),
+            _ => None,
+        }
+    }
+
+    /// Lists the names of every provider for error messages.
+    pub fn names() -> String {
+        Self::ALL.map(Self::as_str).join(", ")
+    }
+
+    /// Returns the error of a PROVIDER setting that names no provider.
+    pub fn missing() -> String {
+        format!("PROVIDER is required; set it to one of {}", Self::names())
+    }
+}
+
+impl str::FromStr for Provider {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(Self::missing());
+        }
+        Self::ALL
+            .into_iter()
+            .find(|provider| provider.as_str() == s)
+            .ok_or_else(|| format!("Unknown provider '{s}'; valid providers: {}", Self::names()))
+    }
+}
+
+impl fmt::Display for Provider {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Route53 => write!(f, "Route 53"),
+            Self::DigitalOcean => write!(f, "DigitalOcean"),
+            Self::GoogleDns => write!(f, "Google Cloud DNS"),
+            Self::Gandi => write!(f, "Gandi"),
+            Self::Hetzner => write!(f, "Hetzner"),
+            Self::DuckDns => write!(f, "DuckDNS"),
+            Self::Namecheap => write!(f, "Namecheap"),
+            Self::Rfc2136 => write!(f, "RFC 2136"),
+            Self::Linode => write!(f, "Linode"),
+            Self::Vultr => write!(f, "Vultr"),
+            Self::Ovh => write!(f, "OVH"),
+            Self::Porkbun => write!(f, "Porkbun"),
+            Self::Desec => write!(f, "deSEC"),
+            Self::Dynu => write!(f, "Dynu"),
+            Self::NoIp => write!(f, "No-IP"),
+        }
+    }
+}
+
+/// Reads whether DynDNS2 updates mark hosts offline rather than updating
+/// them. The record of an offline host may still hold the address, so going
+/// offline takes FORCE_UPDATE.
+fn dyndns_op(settings: &Settings) -> Result<DynDnsOp, DNSUpdateError> {
+    if settings.flag("DYNDNS2_OFFLINE", "dyndns2.offline")? {
+        Ok(DynDnsOp::Offline)
+    } else {
+        Ok(DynDnsOp::Update)
+    }
+}
+
+/// Builds the updater for the provider from its settings.
+pub async fn build_updater(
+    provider: Provider,
+    settings: &Settings,
+    client: &reqwest::Client,
+) -> Result<Box<dyn DNSUpdater>, DNSUpdateError> {
+    Ok(match provider {
+        Provider::Route53 => {
+            let hosted_zone_id: Option<String> =
+                settings.optional("HOSTED_ZONE_ID", "route53.hosted_zone_id")?;
+            let domain: Option<String> = settings.optional("ROUTE53_DOMAIN", "route53.domain")?;
+            let profile_name: Option<String> =
+                settings.optional("AWS_PROFILE_NAME", "route53.profile_name")?;
+            let assume_role_arn: Option<String> =
+                settings.optional("ASSUME_ROLE_ARN", "route53.assume_role_arn")?;
+            let region = settings.optional("AWS_ROUTE53_REGION", "route53.region")?;
+            let endpoint_url: Option<String> =
+                settings.optional("AWS_ROUTE53_ENDPOINT", "route53.endpoint_url")?;
+            let client = route53_client(
+                profile_name.as_deref(),
+                assume_role_arn.as_deref(),
+                region,
+                endpoint_url.as_deref(),
+            )
+            .await?;
+            let hosted_zone_id = match (hosted_zone_id, domain) {
+                (Some(hosted_zone_id), _) => hosted_zone_id,
+                (None, Some(domain)) => hosted_zone_id_by_name(&client, &domain).await?,
+                (None, None) => {
+                    return Err(DNSUpdateError::Config(format!(
+                        "Missing value for {} or {}",
+                        settings.describe("HOSTED_ZONE_ID", "route53.hosted_zone_id"),
+                        settings.describe("ROUTE53_DOMAIN", "route53.domain")
+                    )))
+                }
+            };
+            let set_identifier: Option<String> =
+                settings.optional("ROUTE53_SET_IDENTIFIER", "route53.set_identifier")?;
+            let weight = settings.optional("ROUTE53_WEIGHT", "route53.weight")?;
+            let latency_region =
+                settings.optional("ROUTE53_LATENCY_REGION", "route53.latency_region")?;
+            let routing_policy = match (set_identifier, weight, latency_region) {
+                (None, None, None) => None,
+                (Some(set_identifier), Some(weight), None) => Some(RoutingPolicy::Weighted {
+                    set_identifier,
+                    weight,
+                }),
+                (Some(set_identifier), None, Some(region)) => Some(RoutingPolicy::Latency {
+                    set_identifier,
+                    region,
+                }),
+                _ => {
+                    return Err(DNSUpdateError::Config(String::from(
+                        "ROUTE53_SET_IDENTIFIER needs ROUTE53_WEIGHT or ROUTE53_LATENCY_REGION",
+                    )))
+                }
+            };
+            let reverse_zone_id = if settings.flag("UPDATE_PTR", "route53.update_ptr")? {
+                Some(settings.required("REVERSE_ZONE_ID", "route53.reverse_zone_id")?)
+            } else {
+                None
+            };
+            Box::new(Route53Updater::new(
+                client,
+                hosted_zone_id,
+                routing_policy,
+                reverse_zone_id,
+            ))
+        }
+        Provider::DigitalOcean => Box::new(DigitalOceanUpdater::new(
+            client.clone(),
+            settings.secret("DIGITALOCEAN_TOKEN", "digitalocean.token")?,
+            settings.required("DIGITALOCEAN_DOMAIN", "digitalocean.domain")?,
+            settings.required("DIGITALOCEAN_RECORD_ID", "digitalocean.record_id")?,
+        )),
+        Provider::GoogleDns => Box::new(GoogleDnsUpdater::new(
+            client.clone(),
+            settings.required("GCLOUD_PROJECT", "gcloud.project")?,
+            settings.required("GCLOUD_ZONE", "gcloud.zone")?,
+            settings.required("GOOGLE_APPLICATION_CREDENTIALS", "gcloud.credentials")?,
+        )),
+        Provider::Gandi => Box::new(GandiUpdater::new(
+            client.clone(),
+            settings.secret("GANDI_TOKEN", "gandi.token")?,
+            settings.required("GANDI_DOMAIN", "gandi.domain")?,
+        )),
+        Provider::Hetzner => Box::new(HetznerUpdater::new(
+            client.clone(),
+            settings.secret("HETZNER_TOKEN", "hetzner.token")?,
+            settings.required("HETZNER_ZONE_ID", "hetzner.zone_id")?,
+            settings.required("HETZNER_RECORD_ID", "hetzner.record_id")?,
+        )),
+        Provider::DuckDns => Box::new(DuckDnsUpdater::new(
+            client.clone(),
+            settings.secret("DUCKDNS_TOKEN", "duckdns.token")?,
+        )),
+        Provider::Namecheap => Box::new(NamecheapUpdater::new(
+            client.clone(),
+            settings.required("NAMECHEAP_DOMAIN", "namecheap.domain")?,
+            settings.secret("NAMECHEAP_DDNS_PASSWORD", "namecheap.ddns_password")?,
+        )),
+        Provider::Rfc2136 => Box::new(Rfc2136Updater::new(
+            &settings.required("RFC2136_SERVER", "rfc2136.server")?,
+            &settings.required("RFC2136_ZONE", "rfc2136.zone")?,
+            &settings.required("RFC2136_KEY_NAME", "rfc2136.key_name")?,
+            &settings.secret("RFC2136_KEY_SECRET", "rfc2136.key_secret")?,
+            settings.optional("RFC2136_KEY_ALG", "rfc2136.key_alg")?,
+        )?),
+        Provider::Linode => Box::new(LinodeUpdater::new(
+            client.clone(),
+            settings.secret("LINODE_TOKEN", "linode.token")?,
+            settings.required("LINODE_DOMAIN_ID", "linode.domain_id")?,
+            settings.required("LINODE_RECORD_ID", "linode.record_id")?,
+        )),
+        Provider::Vultr => Box::new(VultrUpdater::new(
+            client.clone(),
+            settings.secret("VULTR_TOKEN", "vultr.token")?,
+            settings.required("VULTR_DOMAIN", "vultr.domain")?,
+            settings.required("VULTR_RECORD_ID", "vultr.record_id")?,
+        )),
+        Provider::Ovh => Box::new(OvhUpdater::new(
+            client.clone(),
+            settings.required("OVH_APP_KEY", "ovh.app_key")?,
+            settings.secret("OVH_APP_SECRET", "ovh.app_secret")?,
+            settings.secret("OVH_CONSUMER_KEY", "ovh.consumer_key")?,
+            settings.required("OVH_ZONE", "ovh.zone")?,
+            settings.required("OVH_RECORD_ID", "ovh.record_id")?,
+        )),
+        Provider::Porkbun => Box::new(PorkbunUpdater::new(
+            client.clone(),
+            settings.secret("PORKBUN_API_KEY", "porkbun.api_key")?,
+            settings.secret("PORKBUN_SECRET", "porkbun.secret")?,
+            settings.required("PORKBUN_DOMAIN", "porkbun.domain")?,
+        )),
+        Provider::Desec => Box::new(DesecUpdater::new(
+            client.clone(),
+            settings.secret("DESEC_TOKEN", "desec.token")?,
+            settings.required("DESEC_DOMAIN", "desec.domain")?,
+        )),
+        Provider::Dynu => Box::new(dynu_updater(
+            client.clone(),
+            settings.required("DYNU_USERNAME", "dynu.username")?,
+            &settings.secret("DYNU_PASSWORD", "dynu.password")?,
+            dyndns_op(settings)?,
+        )),
+        Provider::NoIp => Box::new(noip_updater(
+            client.clone(),
+            settings.required("NOIP_USERNAME", "noip.username")?,
+            settings.secret("NOIP_PASSWORD", "noip.password")?,
+            dyndns_op(settings)?,
+        )),
+    })
+}
+
+/// Sends requests to provider APIs, logging each request and its response at
+/// debug level with the secrets replaced.
+#[async_trait]
+trait SendLogged {
+    async fn send_logged(self, secrets: &[&str]) -> reqwest::Result<reqwest::Response>;
+}
+
+#[async_trait]
+impl SendLogged for reqwest::RequestBuilder {
+    async fn send_logged(self, secrets: &[&str]) -> reqwest::Result<reqwest::Response> {
+        if !tracing::enabled!(Level::DEBUG) {
+            return self.send().await;
+        }
+        if let Some(request) = self.try_clone().and_then(|builder| builder.build().ok()) {
+            let body = request
+                .body()
+                .and_then(reqwest::Body::as_bytes)
+                .map(String::from_utf8_lossy)
+                .unwrap_or_default();
+            debug!(
+                method = %request.method(),
+                url = redact_url(request.url(), secrets),
+                body = redact(&body, secrets),
+                "Sending request"
+            );
+        }
+
+        // Reading the body for the log consumes the response, so rebuild it
+        let response = self.send().await?;
+        let status = response.status();
+        let version = response.version();
+        let url = response.url().clone();
+        let headers = response.headers().clone();
+        let body = response.bytes().await?;
+        debug!(
+            %status,
+            body = redact(&String::from_utf8_lossy(&body), secrets),
+            "Received response"
+        );
+        let mut rebuilt = http::Response::builder()
+            .url(url)
+            .body(body)
+            .expect("a response with only a URL set is valid");
+        *rebuilt.status_mut() = status;
+        *rebuilt.version_mut() = version;
+        *rebuilt.headers_mut() = headers;
+        Ok(reqwest::Response::from(rebuilt))
+    }
+}
+
+/// Replaces every occurrence of the secrets in the text.
+fn redact(text: &str, secrets: &[&str]) -> String {
+    secrets
+        .iter()
+        .filter(|secret| !secret.is_empty())
+        .fold(String::from(text), |text, secret| {
+            text.replace(secret, "[redacted]")
+        })
+}
+
+/// Replaces the secrets in the URL, including percent-encoded query values.
+fn redact_url(url: &reqwest::Url, secrets: &[&str]) -> String {
+    let mut url = url.clone();
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(name, value)| (name.into_owned(), redact(&value, secrets)))
+        .collect();
+    if !pairs.is_empty() {
+        url.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    redact(url.as_str(), secrets)
+}
+
+/// Describes a failed request to the API of the provider.
+fn http_error(provider: Provider, e: reqwest::Error) -> DNSUpdateError {
+    DNSUpdateError::Http {
+        provider,
+        status: e.status().map(|status| status.as_u16()),
+        message: e.to_string(),
+    }
+}
+
+/// Returns the record value in zone file form, which quotes TXT values and
+/// fully qualifies CNAME targets.
+fn record_data(record_type: RecordType, record_value: &str) -> String {
+    match record_type {
+        RecordType::Txt => format!(
+            "\"{}\"",
+            record_value.replace('\\', "\\\\").replace('"', "\\\"")
+        ),
+        RecordType::Cname => format!("{}.", record_value.trim_end_matches('.')),
+        RecordType::A | RecordType::Aaaa => String::from(record_value),
+    }
+}
+
+/// Reverses record_data, leaving values that aren't quoted as they are.
+fn record_value(record_type: RecordType, record_data: &str) -> String {
+    if record_type == RecordType::Cname {
+        return String::from(record_data.trim_end_matches('.'));
+    }
+    match record_data
+        .strip_prefix('"')
+        .and_then(|data| data.strip_suffix('"'))
+    {
+        Some(data) if record_type == RecordType::Txt => {
+            data.replace("\\\"", "\"").replace("\\\\", "\\")
+        }
+        _ => String::from(record_data),
+    }
+}
+
+/// Returns the host name relative to the domain, or "@" for the domain apex.
+/// Hosts outside the domain fail, since the provider would otherwise create
+/// them as names under it.
+fn relative_name(host_name: &str, domain: &str) -> Result<String, DNSUpdateError> {
+    let host_name = host_name.trim_end_matches('.');
+    let domain = domain.trim_end_matches('.');
+    if host_name == domain {
+        return Ok(String::from("@"));
+    }
+    // The dot before the domain keeps e.g. notexample.com out of example.com
+    host_name
+        .strip_suffix(domain)
+        .and_then(|name| name.strip_suffix('.'))
+        .filter(|name| !name.is_empty())
+        .map(String::from)
+        .ok_or_else(|| {
+            DNSUpdateError::Config(format!("Host {host_name} isn't in the domain {domain}"))
+        })
+}
@@ -0,0 +1,89 @@
+use super::{relative_name, DNSUpdater, DnsRecord, RecordType, SendLogged};
+use crate::xml::element;
+use crate::DNSUpdateError;
+use async_trait::async_trait;
+use std::error;
+
+const API_URL: &str = "https://dynamicdns.park-your-domain.com/update";
+
+fn namecheap_error(e: impl Into<Box<dyn error::Error + Send + Sync>>) -> DNSUpdateError {
+    DNSUpdateError::Namecheap(e.into())
+}
+
+pub struct NamecheapUpdater {
+    client: reqwest::Client,
+    api_url: String,
+    domain: String,
+    password: String,
+}
+
+impl NamecheapUpdater {
+    pub fn new(client: reqwest::Client, domain: String, password: String) -> Self {
+        Self {
+            client,
+            api_url: String::from(API_URL),
+            domain,
+            password,
+        }
+    }
+
+    /// Sends requests to the API at the URL rather than Namecheap's, such as a
+    /// proxy or a test server.
+    pub fn with_api_url(mut self, api_url: &str) -> Self {
+        self.api_url = String::from(api_url.trim_end_matches('/'));
+        self
+    }
+}
+
+#[async_trait]
+impl DNSUpdater for NamecheapUpdater {
+    async fn update(&self, record: DnsRecord) -> Result<(), DNSUpdateError> {
+        if record.rtype != RecordType::A {
+            return Err(DNSUpdateError::Config(format!(
+                "Namecheap dynamic DNS only supports A records, not {}",
+                record.rtype
+            )));
+        }
+        let host = relative_name(&record.name, &self.domain)?;
+        let body = self
+            .client
+            .get(&self.api_url)
+            .query(&[
+                ("host", host.as_str()),
+                ("domain", &self.domain),
+                ("password", &self.password),
+                ("ip", &record.value),
+            ])
+            .send_logged(&[&self.password])
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(namecheap_error)?
+            .text()
+            .await
+            .map_err(namecheap_error)?;
+        match element(&body, "ErrCount") {
+            Some("0") => Ok(()),
+            Some(_) => Err(namecheap_error(format!(
+                "update of {host} rejected with {}",
+                element(&body, "Err1").unwrap_or("an unknown error")
+            ))),
+            None => Err(namecheap_error(format!(
+                "unexpected response to update of {host}: {body}"
+            ))),
+        }
+    }
+
+    async fn current_record(
+        &self,
+        _host_name: &str,
+        _record_type: RecordType,
+    ) -> Result<Option<String>, DNSUpdateError> {
+        Err(DNSUpdateError::Config(String::from(
+            "Namecheap records can't be read back; use COMPARE_SOURCE=resolver",
+        )))
+    }
+
+    fn can_read(&self) -> bool {
+        false
+    }
+}
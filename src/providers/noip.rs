@@ -0,0 +1,21 @@
+use super::dyndns2::{DynDns2Updater, DynDnsOp, Fields};
+use super::Provider;
+
+const API_URL: &str = "https://dynupdate.no-ip.com/nic/update";
+
+pub fn noip_updater(
+    client: reqwest::Client,
+    username: String,
+    password: String,
+    op: DynDnsOp,
+) -> DynDns2Updater {
+    DynDns2Updater::new(
+        client,
+        Provider::NoIp,
+        API_URL,
+        Fields::STANDARD,
+        username,
+        password,
+        op,
+    )
+}
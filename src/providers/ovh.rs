@@ -0,0 +1,159 @@
+use super::{
+    http_error, record_data, record_value, DNSUpdater, DnsRecord, Provider, RecordType, SendLogged,
+};
+use crate::DNSUpdateError;
+use async_trait::async_trait;
+use ring::digest::{digest, SHA1_FOR_LEGACY_USE_ONLY};
+use serde::Deserialize;
+use std::fmt::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const API_URL: &str = "https://eu.api.ovh.com/1.0";
+
+#[derive(Deserialize)]
+struct Record {
+    target: String,
+}
+
+fn ovh_error(e: reqwest::Error) -> DNSUpdateError {
+    http_error(Provider::Ovh, e)
+}
+
+/// Computes the X-Ovh-Signature header for a request made at the timestamp,
+/// in seconds since the Unix epoch.
+pub fn ovh_signature(
+    app_secret: &str,
+    consumer_key: &str,
+    method: &str,
+    url: &str,
+    body: &str,
+    timestamp: u64,
+) -> String {
+    let payload = format!("{app_secret}+{consumer_key}+{method}+{url}+{body}+{timestamp}");
+    digest(&SHA1_FOR_LEGACY_USE_ONLY, payload.as_bytes())
+        .as_ref()
+        .iter()
+        .fold(String::from("$1$"), |mut signature, byte| {
+            write!(signature, "{byte:02x}").unwrap();
+            signature
+        })
+}
+
+pub struct OvhUpdater {
+    client: reqwest::Client,
+    api_url: String,
+    app_key: String,
+    app_secret: String,
+    consumer_key: String,
+    zone: String,
+    record_id: String,
+}
+
+impl OvhUpdater {
+    pub fn new(
+        client: reqwest::Client,
+        app_key: String,
+        app_secret: String,
+        consumer_key: String,
+        zone: String,
+        record_id: String,
+    ) -> Self {
+        Self {
+            client,
+            api_url: String::from(API_URL),
+            app_key,
+            app_secret,
+            consumer_key,
+            zone,
+            record_id,
+        }
+    }
+
+    /// Sends requests to the API at the URL rather than OVH's, such as a
+    /// proxy or a test server.
+    pub fn with_api_url(mut self, api_url: &str) -> Self {
+        self.api_url = String::from(api_url.trim_end_matches('/'));
+        self
+    }
+
+    /// Sends a request signed with the application and consumer keys.
+    async fn send(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<serde_json::Value>,
+    ) -> Result<reqwest::Response, DNSUpdateError> {
+        let url = format!("{}{path}", self.api_url);
+        let body = body.map_or_else(String::new, |body| body.to_string());
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        let signature = ovh_signature(
+            &self.app_secret,
+            &self.consumer_key,
+            method.as_str(),
+            &url,
+            &body,
+            timestamp,
+        );
+        self.client
+            .request(method, &url)
+            .header("X-Ovh-Application", &self.app_key)
+            .header("X-Ovh-Consumer", &self.consumer_key)
+            .header("X-Ovh-Timestamp", timestamp)
+            .header("X-Ovh-Signature", signature)
+            .header("Content-Type", "application/json")
+            .body(body)
+            .send_logged(&[&self.app_secret, &self.consumer_key])
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(ovh_error)
+    }
+}
+
+#[async_trait]
+impl DNSUpdater for OvhUpdater {
+    async fn update(&self, record: DnsRecord) -> Result<(), DNSUpdateError> {
+        // TXT values are quoted by the API, while CNAME targets have to be
+        // fully qualified
+        let target = match record.rtype {
+            RecordType::Cname => record_data(record.rtype, &record.value),
+            _ => record.value,
+        };
+        self.send(
+            reqwest::Method::PUT,
+            &format!("/domain/zone/{}/record/{}", self.zone, self.record_id),
+            Some(serde_json::json!({
+                "target": target,
+                "ttl": record.ttl,
+            })),
+        )
+        .await?;
+        // Changes only take effect once the zone is refreshed
+        self.send(
+            reqwest::Method::POST,
+            &format!("/domain/zone/{}/refresh", self.zone),
+            None,
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn current_record(
+        &self,
+        _host_name: &str,
+        record_type: RecordType,
+    ) -> Result<Option<String>, DNSUpdateError> {
+        let record: Record = self
+            .send(
+                reqwest::Method::GET,
+                &format!("/domain/zone/{}/record/{}", self.zone, self.record_id),
+                None,
+            )
+            .await?
+            .json()
+            .await
+            .map_err(ovh_error)?;
+        Ok(Some(record_value(record_type, &record.target)))
+    }
+}
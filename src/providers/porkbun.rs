@@ -0,0 +1,149 @@
+use super::{http_error, relative_name, DNSUpdater, DnsRecord, Provider, RecordType, SendLogged};
+use crate::DNSUpdateError;
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+const API_URL: &str = "https://api.porkbun.com/api/json/v3";
+/// The lowest TTL Porkbun accepts, in seconds.
+const MIN_TTL: u32 = 600;
+
+#[derive(Deserialize)]
+struct Status {
+    status: String,
+    #[serde(default)]
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct Record {
+    content: String,
+}
+
+#[derive(Deserialize)]
+struct RecordsResponse {
+    records: Vec<Record>,
+}
+
+fn porkbun_error(e: reqwest::Error) -> DNSUpdateError {
+    http_error(Provider::Porkbun, e)
+}
+
+pub struct PorkbunUpdater {
+    client: reqwest::Client,
+    api_url: String,
+    api_key: String,
+    secret: String,
+    domain: String,
+}
+
+impl PorkbunUpdater {
+    pub fn new(client: reqwest::Client, api_key: String, secret: String, domain: String) -> Self {
+        Self {
+            client,
+            api_url: String::from(API_URL),
+            api_key,
+            secret,
+            domain,
+        }
+    }
+
+    /// Sends requests to the API at the URL rather than Porkbun's, such as a
+    /// proxy or a test server.
+    pub fn with_api_url(mut self, api_url: &str) -> Self {
+        self.api_url = String::from(api_url.trim_end_matches('/'));
+        self
+    }
+
+    /// Returns the path of the records of the type for the host, e.g.
+    /// "example.com/A/www". The apex has an empty subdomain.
+    fn record_path(
+        &self,
+        host_name: &str,
+        record_type: RecordType,
+    ) -> Result<String, DNSUpdateError> {
+        let subdomain = match relative_name(host_name, &self.domain)?.as_str() {
+            "@" => String::new(),
+            subdomain => String::from(subdomain),
+        };
+        Ok(format!(
+            "{}/{}/{subdomain}",
+            self.domain,
+            record_type.as_str()
+        ))
+    }
+
+    /// Posts the request with the keys added to the body, failing unless
+    /// Porkbun reports success.
+    async fn post<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        mut body: serde_json::Value,
+    ) -> Result<T, DNSUpdateError> {
+        body["apikey"] = self.api_key.clone().into();
+        body["secretapikey"] = self.secret.clone().into();
+        // Errors come with a JSON body, so check it before the HTTP status
+        let response = self
+            .client
+            .post(format!("{}/{path}", self.api_url))
+            .json(&body)
+            .send_logged(&[&self.api_key, &self.secret])
+            .await
+            .map_err(porkbun_error)?;
+        let http_status = response.status().as_u16();
+        let invalid = |message: String| DNSUpdateError::Http {
+            provider: Provider::Porkbun,
+            status: Some(http_status),
+            message,
+        };
+        let body: serde_json::Value = response.json().await.map_err(porkbun_error)?;
+        let status = Status::deserialize(&body).map_err(|e| invalid(e.to_string()))?;
+        if status.status != "SUCCESS" {
+            return Err(invalid(format!("{}: {}", status.status, status.message)));
+        }
+        T::deserialize(body).map_err(|e| invalid(e.to_string()))
+    }
+}
+
+#[async_trait]
+impl DNSUpdater for PorkbunUpdater {
+    async fn update(&self, record: DnsRecord) -> Result<(), DNSUpdateError> {
+        self.post::<Status>(
+            &format!(
+                "dns/editByNameType/{}",
+                self.record_path(&record.name, record.rtype)?
+            ),
+            serde_json::json!({
+                "content": record.value,
+                "ttl": record.ttl.to_string(),
+            }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn current_record(
+        &self,
+        host_name: &str,
+        record_type: RecordType,
+    ) -> Result<Option<String>, DNSUpdateError> {
+        let response: RecordsResponse = self
+            .post(
+                &format!(
+                    "dns/retrieveByNameType/{}",
+                    self.record_path(host_name, record_type)?
+                ),
+                serde_json::json!({}),
+            )
+            .await?;
+        Ok(response
+            .records
+            .into_iter()
+            .next()
+            .map(|record| record.content))
+    }
+
+    fn min_ttl(&self) -> u32 {
+        MIN_TTL
+    }
+}
@@ -0,0 +1,173 @@
+use super::{DNSUpdater, DnsRecord, RecordType};
+use crate::DNSUpdateError;
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hickory_client::client::{AsyncClient, ClientHandle};
+use hickory_client::op::{Message, ResponseCode, UpdateMessage};
+use hickory_client::proto::op::update_message;
+use hickory_client::proto::rr::dnssec::rdata::tsig::TsigAlgorithm;
+use hickory_client::proto::rr::dnssec::tsig::TSigner;
+use hickory_client::proto::xfer::{DnsHandle, FirstAnswer};
+use hickory_client::rr::rdata::{A, AAAA, CNAME, TXT};
+use hickory_client::rr::{DNSClass, Name, RData, Record};
+use hickory_client::udp::UdpClientStream;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use std::{error, str};
+use tokio::net::UdpSocket;
+
+const DEFAULT_PORT: u16 = 53;
+const DEFAULT_KEY_ALG: &str = "hmac-sha256";
+/// Allowed difference between the client and server clocks, in seconds.
+const FUDGE: u16 = 300;
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn rfc2136_error(e: impl Into<Box<dyn error::Error + Send + Sync>>) -> DNSUpdateError {
+    DNSUpdateError::Rfc2136(e.into())
+}
+
+fn fully_qualified(name: &str) -> Result<Name, DNSUpdateError> {
+    let mut name = Name::from_ascii(name).map_err(rfc2136_error)?;
+    name.set_fqdn(true);
+    Ok(name)
+}
+
+fn hickory_record_type(record_type: RecordType) -> hickory_client::rr::RecordType {
+    match record_type {
+        RecordType::A => hickory_client::rr::RecordType::A,
+        RecordType::Aaaa => hickory_client::rr::RecordType::AAAA,
+        RecordType::Txt => hickory_client::rr::RecordType::TXT,
+        RecordType::Cname => hickory_client::rr::RecordType::CNAME,
+    }
+}
+
+/// Builds the UPDATE message replacing the record set of the name in the zone
+/// with the single record.
+fn replace_message(zone: Name, record: Record) -> Message {
+    let mut message = update_message::delete_rrset(record.clone(), zone, false);
+    message.add_update(record);
+    message
+}
+
+pub struct Rfc2136Updater {
+    server: SocketAddr,
+    zone: Name,
+    signer: TSigner,
+}
+
+impl Rfc2136Updater {
+    pub fn new(
+        server: &str,
+        zone: &str,
+        key_name: &str,
+        key_secret: &str,
+        key_alg: Option<String>,
+    ) -> Result<Self, DNSUpdateError> {
+        let server = server
+            .parse()
+            .or_else(|_| {
+                server
+                    .parse::<IpAddr>()
+                    .map(|ip| SocketAddr::new(ip, DEFAULT_PORT))
+            })
+            .map_err(|e| {
+                DNSUpdateError::Config(format!("Invalid RFC 2136 server {server}: {e}"))
+            })?;
+        let key = STANDARD
+            .decode(key_secret)
+            .map_err(|e| DNSUpdateError::Config(format!("Invalid RFC 2136 key secret: {e}")))?;
+        let key_alg = key_alg.as_deref().unwrap_or(DEFAULT_KEY_ALG);
+        let algorithm = TsigAlgorithm::from_name(Name::from_ascii(key_alg).map_err(|e| {
+            DNSUpdateError::Config(format!("Invalid RFC 2136 key algorithm {key_alg}: {e}"))
+        })?);
+        let signer = TSigner::new(key, algorithm, fully_qualified(key_name)?, FUDGE)
+            .map_err(|e| DNSUpdateError::Config(format!("Invalid RFC 2136 key: {e}")))?;
+        Ok(Self {
+            server,
+            zone: fully_qualified(zone)?,
+            signer,
+        })
+    }
+
+    async fn client(&self) -> Result<AsyncClient, DNSUpdateError> {
+        let stream = UdpClientStream::<UdpSocket, TSigner>::with_timeout_and_signer(
+            self.server,
+            TIMEOUT,
+            Some(Arc::new(self.signer.clone())),
+        );
+        let (client, background) = AsyncClient::connect(stream).await.map_err(rfc2136_error)?;
+        tokio::spawn(background);
+        Ok(client)
+    }
+
+    /// Builds the UPDATE message replacing the record set of the record's name
+    /// in the zone with the record alone.
+    pub fn update_message(&self, record: &DnsRecord) -> Result<Message, DNSUpdateError> {
+        let rdata = match record.rtype {
+            RecordType::A => RData::A(A(record.value.parse()?)),
+            RecordType::Aaaa => RData::AAAA(AAAA(record.value.parse()?)),
+            RecordType::Txt => RData::TXT(TXT::new(vec![record.value.clone()])),
+            RecordType::Cname => RData::CNAME(CNAME(fully_qualified(&record.value)?)),
+        };
+        let resource = Record::from_rdata(fully_qualified(&record.name)?, record.ttl, rdata);
+        Ok(replace_message(self.zone.clone(), resource))
+    }
+}
+
+#[async_trait]
+impl DNSUpdater for Rfc2136Updater {
+    async fn update(&self, record: DnsRecord) -> Result<(), DNSUpdateError> {
+        let message = self.update_message(&record)?;
+        let response = self
+            .client()
+            .await?
+            .send(message)
+            .first_answer()
+            .await
+            .map_err(rfc2136_error)?;
+        match response.response_code() {
+            ResponseCode::NoError => Ok(()),
+            code => Err(rfc2136_error(format!(
+                "update of {} rejected with {code}",
+                record.name
+            ))),
+        }
+    }
+
+    async fn current_record(
+        &self,
+        host_name: &str,
+        record_type: RecordType,
+    ) -> Result<Option<String>, DNSUpdateError> {
+        let response = self
+            .client()
+            .await?
+            .query(
+                fully_qualified(host_name)?,
+                DNSClass::IN,
+                hickory_record_type(record_type),
+            )
+            .await
+            .map_err(rfc2136_error)?;
+        Ok(response
+            .answers()
+            .iter()
+            .filter_map(Record::data)
+            .find_map(|data| match data {
+                RData::A(A(ip)) => Some(ip.to_string()),
+                RData::AAAA(AAAA(ip)) => Some(ip.to_string()),
+                RData::CNAME(CNAME(target)) => {
+                    Some(String::from(target.to_ascii().trim_end_matches('.')))
+                }
+                RData::TXT(txt) => Some(
+                    txt.txt_data()
+                        .iter()
+                        .map(|part| String::from_utf8_lossy(part))
+                        .collect(),
+                ),
+                _ => None,
+            }))
+    }
+}
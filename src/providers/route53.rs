@@ -0,0 +1,301 @@
+use super::{record_data, record_value, DNSUpdater, DnsRecord, RecordType};
+use crate::DNSUpdateError;
+use async_trait::async_trait;
+use aws_config::profile::profile_file::ProfileFiles;
+use aws_sdk_route53::types;
+use aws_types::os_shim_internal::{Env, Fs};
+use std::net::IpAddr;
+use tracing::debug;
+
+/// Returns the name of the PTR record of the address in its reverse zone.
+pub fn reverse_name(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, d] = ip.octets();
+            format!("{d}.{c}.{b}.{a}.in-addr.arpa")
+        }
+        IpAddr::V6(ip) => {
+            let mut name = String::new();
+            for byte in ip.octets().iter().rev() {
+                name.push_str(&format!("{:x}.{:x}.", byte & 0xf, byte >> 4));
+            }
+            name + "ip6.arpa"
+        }
+    }
+}
+
+fn upsert(
+    name: String,
+    rtype: types::RrType,
+    value: String,
+    ttl: u32,
+    routing_policy: Option<&RoutingPolicy>,
+) -> Result<types::Change, DNSUpdateError> {
+    let resource_record = types::ResourceRecord::builder().value(value).build()?;
+    let mut builder = types::ResourceRecordSet::builder()
+        .name(name)
+        .ttl(i64::from(ttl))
+        .r#type(rtype)
+        .resource_records(resource_record);
+    if let Some(routing_policy) = routing_policy {
+        builder = routing_policy.apply(builder);
+    }
+    let resource_record_set = builder.build()?;
+    Ok(types::Change::builder()
+        .action(types::ChangeAction::Upsert)
+        .resource_record_set(resource_record_set)
+        .build()?)
+}
+
+/// How Route 53 chooses between the records of a name and type, each of which
+/// is told apart by its set identifier.
+pub enum RoutingPolicy {
+    Weighted {
+        set_identifier: String,
+        weight: i64,
+    },
+    Latency {
+        set_identifier: String,
+        region: String,
+    },
+}
+
+impl RoutingPolicy {
+    fn set_identifier(&self) -> &str {
+        match self {
+            Self::Weighted { set_identifier, .. } | Self::Latency { set_identifier, .. } => {
+                set_identifier
+            }
+        }
+    }
+
+    /// Sets the policy's fields on the record set.
+    fn apply(
+        &self,
+        builder: types::builders::ResourceRecordSetBuilder,
+    ) -> types::builders::ResourceRecordSetBuilder {
+        let builder = builder.set_identifier(self.set_identifier());
+        match self {
+            Self::Weighted { weight, .. } => builder.weight(*weight),
+            Self::Latency { region, .. } => {
+                builder.region(types::ResourceRecordSetRegion::from(region.as_str()))
+            }
+        }
+    }
+}
+
+pub struct Route53Updater {
+    client: aws_sdk_route53::Client,
+    hosted_zone_id: String,
+    /// The policy of the host's records, unless they're simple records
+    routing_policy: Option<RoutingPolicy>,
+    /// The zone of the PTR records pointing back at the hosts, if they're kept
+    /// up to date
+    reverse_zone_id: Option<String>,
+}
+
+impl Route53Updater {
+    pub fn new(
+        client: aws_sdk_route53::Client,
+        hosted_zone_id: String,
+        routing_policy: Option<RoutingPolicy>,
+        reverse_zone_id: Option<String>,
+    ) -> Self {
+        Self {
+            client,
+            hosted_zone_id,
+            routing_policy,
+            reverse_zone_id,
+        }
+    }
+
+    /// Sends the changes to the zone in a single batch, which Route 53 applies
+    /// all at once or not at all.
+    async fn submit(
+        &self,
+        hosted_zone_id: &str,
+        changes: Vec<types::Change>,
+    ) -> Result<(), DNSUpdateError> {
+        let change_batch = types::ChangeBatch::builder()
+            .set_changes(Some(changes))
+            .build()?;
+        self.client
+            .change_resource_record_sets()
+            .hosted_zone_id(hosted_zone_id)
+            .change_batch(change_batch)
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|e| DNSUpdateError::Route53(Box::new(e.into())))
+    }
+}
+
+#[async_trait]
+impl DNSUpdater for Route53Updater {
+    async fn update(&self, record: DnsRecord) -> Result<(), DNSUpdateError> {
+        self.update_batch(vec![record]).await
+    }
+
+    async fn update_batch(&self, records: Vec<DnsRecord>) -> Result<(), DNSUpdateError> {
+        let mut changes = Vec::new();
+        let mut reverse_changes = Vec::new();
+        for record in &records {
+            changes.push(upsert(
+                record.name.clone(),
+                types::RrType::from(record.rtype.as_str()),
+                record_data(record.rtype, &record.value),
+                record.ttl,
+                self.routing_policy.as_ref(),
+            )?);
+
+            // Only address records have a reverse
+            if self.reverse_zone_id.is_none() || !record.rtype.is_address() {
+                continue;
+            }
+            let reverse_name = reverse_name(record.value.parse()?);
+            debug!(reverse_name, host_name = record.name, "Updating PTR record");
+            reverse_changes.push(upsert(
+                reverse_name,
+                types::RrType::Ptr,
+                format!("{}.", record.name.trim_end_matches('.')),
+                record.ttl,
+                None,
+            )?);
+        }
+        self.submit(&self.hosted_zone_id, changes).await?;
+        match &self.reverse_zone_id {
+            Some(reverse_zone_id) if !reverse_changes.is_empty() => {
+                self.submit(reverse_zone_id, reverse_changes).await
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn can_batch(&self) -> bool {
+        true
+    }
+
+    async fn current_record(
+        &self,
+        host_name: &str,
+        record_type: RecordType,
+    ) -> Result<Option<String>, DNSUpdateError> {
+        let set_identifier = self
+            .routing_policy
+            .as_ref()
+            .map(RoutingPolicy::set_identifier);
+        let output = self
+            .client
+            .list_resource_record_sets()
+            .hosted_zone_id(&self.hosted_zone_id)
+            .start_record_name(host_name)
+            .start_record_type(types::RrType::from(record_type.as_str()))
+            .set_start_record_identifier(set_identifier.map(String::from))
+            .max_items(1)
+            .send()
+            .await
+            .map_err(|e| DNSUpdateError::Route53(Box::new(e.into())))?;
+        Ok(output
+            .resource_record_sets()
+            .iter()
+            .find(|set| {
+                set.name()
+                    .trim_end_matches('.')
+                    .eq_ignore_ascii_case(host_name.trim_end_matches('.'))
+                    && set.r#type().as_str() == record_type.as_str()
+                    && set.set_identifier() == set_identifier
+            })
+            .and_then(|set| set.resource_records().first())
+            .map(|record| record_value(record_type, record.value())))
+    }
+}
+
+/// Looks up the ID of the hosted zone of the domain, failing unless exactly one
+/// zone has that name, as when there are public and private zones for it.
+pub async fn hosted_zone_id_by_name(
+    client: &aws_sdk_route53::Client,
+    domain: &str,
+) -> Result<String, DNSUpdateError> {
+    let domain = domain.trim_end_matches('.');
+    let output = client
+        .list_hosted_zones_by_name()
+        .dns_name(domain)
+        .send()
+        .await
+        .map_err(|e| DNSUpdateError::Route53(Box::new(e.into())))?;
+    // Zones are listed in order of name starting from the domain, so any with
+    // its name come first
+    let ids: Vec<&str> = output
+        .hosted_zones()
+        .iter()
+        .filter(|zone| {
+            zone.name()
+                .trim_end_matches('.')
+                .eq_ignore_ascii_case(domain)
+        })
+        .map(|zone| zone.id().trim_start_matches("/hostedzone/"))
+        .collect();
+    match ids.as_slice() {
+        [id] => {
+            debug!(domain, hosted_zone_id = id, "Found hosted zone");
+            Ok(String::from(*id))
+        }
+        [] => Err(DNSUpdateError::Config(format!(
+            "No Route 53 hosted zone is named {domain}"
+        ))),
+        _ => Err(DNSUpdateError::Config(format!(
+            "Several Route 53 hosted zones are named {domain} ({}); set HOSTED_ZONE_ID to pick one",
+            ids.join(", ")
+        ))),
+    }
+}
+
+/// Creates a client using the credentials of the named profile, or else the
+/// default credentials, assuming the role first if one is given. The region
+/// override also applies to the role's STS calls, since the region decides the
+/// partition, e.g. GovCloud or China, that the role lives in; the endpoint
+/// override only applies to Route 53. A named profile must be defined in the
+/// AWS config or credentials file.
+pub async fn route53_client(
+    profile_name: Option<&str>,
+    assume_role_arn: Option<&str>,
+    region: Option<String>,
+    endpoint_url: Option<&str>,
+) -> Result<aws_sdk_route53::Client, DNSUpdateError> {
+    if let Some(profile_name) = profile_name {
+        let profiles =
+            aws_config::profile::load(&Fs::real(), &Env::real(), &ProfileFiles::default(), None)
+                .await
+                .map_err(|err| {
+                    DNSUpdateError::Config(format!("Can't load the AWS profiles: {err}"))
+                })?;
+        if profiles.get_profile(profile_name).is_none() {
+            return Err(DNSUpdateError::Config(format!(
+                "No AWS profile is named {profile_name}"
+            )));
+        }
+    }
+    let loader = || {
+        let mut loader = aws_config::defaults(aws_config::BehaviorVersion::v2023_11_09());
+        if let Some(profile_name) = profile_name {
+            loader = loader.profile_name(profile_name);
+        }
+        match &region {
+            Some(region) => loader.region(aws_config::Region::new(region.clone())),
+            None => loader,
+        }
+    };
+    let mut config = loader().load().await;
+    if let Some(assume_role_arn) = assume_role_arn {
+        let provider = aws_config::sts::AssumeRoleProvider::builder(assume_role_arn)
+            .configure(&config)
+            .build()
+            .await;
+        config = loader().credentials_provider(provider).load().await;
+    }
+    let mut builder = aws_sdk_route53::config::Builder::from(&config);
+    if let Some(endpoint_url) = endpoint_url {
+        builder = builder.endpoint_url(endpoint_url);
+    }
+    Ok(aws_sdk_route53::Client::from_conf(builder.build()))
+}
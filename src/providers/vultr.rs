@@ -0,0 +1,115 @@
+use super::{
+    http_error, record_data, record_value, DNSUpdater, DnsRecord, Provider, RecordType, SendLogged,
+};
+use crate::DNSUpdateError;
+use async_trait::async_trait;
+use serde::Deserialize;
+
+const API_URL: &str = "https://api.vultr.com/v2";
+
+#[derive(Deserialize)]
+struct Record {
+    data: String,
+}
+
+#[derive(Deserialize)]
+struct RecordResponse {
+    record: Record,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+fn vultr_error(e: reqwest::Error) -> DNSUpdateError {
+    http_error(Provider::Vultr, e)
+}
+
+/// Returns the response if it succeeded, or else the error message Vultr gave
+/// for it.
+async fn check(response: reqwest::Response) -> Result<reqwest::Response, DNSUpdateError> {
+    let Err(e) = response.error_for_status_ref() else {
+        return Ok(response);
+    };
+    // Keep the HTTP error for failures worth retrying
+    if crate::is_retryable_http(&e) {
+        return Err(vultr_error(e));
+    }
+    match response.json::<ErrorResponse>().await {
+        Ok(body) if !body.error.is_empty() => Err(DNSUpdateError::Http {
+            provider: Provider::Vultr,
+            status: e.status().map(|status| status.as_u16()),
+            message: format!("{} ({})", body.error, e.status().unwrap_or_default()),
+        }),
+        _ => Err(vultr_error(e)),
+    }
+}
+
+pub struct VultrUpdater {
+    client: reqwest::Client,
+    api_url: String,
+    token: String,
+    domain: String,
+    record_id: String,
+}
+
+impl VultrUpdater {
+    pub fn new(client: reqwest::Client, token: String, domain: String, record_id: String) -> Self {
+        Self {
+            client,
+            api_url: String::from(API_URL),
+            token,
+            domain,
+            record_id,
+        }
+    }
+
+    /// Sends requests to the API at the URL rather than Vultr's, such as a
+    /// proxy or a test server.
+    pub fn with_api_url(mut self, api_url: &str) -> Self {
+        self.api_url = String::from(api_url.trim_end_matches('/'));
+        self
+    }
+
+    fn record_url(&self) -> String {
+        format!(
+            "{}/domains/{}/records/{}",
+            self.api_url, self.domain, self.record_id
+        )
+    }
+}
+
+#[async_trait]
+impl DNSUpdater for VultrUpdater {
+    async fn update(&self, record: DnsRecord) -> Result<(), DNSUpdateError> {
+        let response = self
+            .client
+            .patch(self.record_url())
+            .bearer_auth(&self.token)
+            .json(&serde_json::json!({
+                "data": record_data(record.rtype, &record.value),
+                "ttl": record.ttl,
+            }))
+            .send_logged(&[&self.token])
+            .await
+            .map_err(vultr_error)?;
+        check(response).await.map(|_| ())
+    }
+
+    async fn current_record(
+        &self,
+        _host_name: &str,
+        record_type: RecordType,
+    ) -> Result<Option<String>, DNSUpdateError> {
+        let response = self
+            .client
+            .get(self.record_url())
+            .bearer_auth(&self.token)
+            .send_logged(&[&self.token])
+            .await
+            .map_err(vultr_error)?;
+        let response: RecordResponse = check(response).await?.json().await.map_err(vultr_error)?;
+        Ok(Some(record_value(record_type, &response.record.data)))
+    }
+}
@@ -0,0 +1,42 @@
+use crate::providers::{Provider, RecordType};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_MIN_UPDATE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Allows at most one update of each record at each provider within the
+/// interval, to stay clear of provider rate limits in daemon mode.
+pub struct RateLimiter {
+    min_interval: Duration,
+    last_updates: Mutex<HashMap<(Provider, String, RecordType), Instant>>,
+}
+
+impl RateLimiter {
+    pub fn new(min_interval: Duration) -> Self {
+        Self {
+            min_interval,
+            last_updates: Mutex::default(),
+        }
+    }
+
+    /// Returns whether the record may be updated now, counting it as updated
+    /// if so.
+    pub fn try_acquire(
+        &self,
+        provider: Provider,
+        host_name: &str,
+        record_type: RecordType,
+    ) -> bool {
+        let now = Instant::now();
+        let mut last_updates = self.last_updates.lock().unwrap();
+        let key = (provider, String::from(host_name), record_type);
+        match last_updates.get(&key) {
+            Some(last) if now.duration_since(*last) < self.min_interval => false,
+            _ => {
+                last_updates.insert(key, now);
+                true
+            }
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use crate::providers::RecordType;
+use async_trait::async_trait;
+use hickory_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use hickory_resolver::error::ResolveErrorKind;
+use hickory_resolver::TokioAsyncResolver;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use tokio::net::lookup_host;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AddressFamily {
+    V4,
+    V6,
+    Any,
+}
+
+impl AddressFamily {
+    /// Returns the family of the addresses stored in records of the type.
+    pub fn for_record_type(record_type: RecordType) -> Self {
+        match record_type {
+            RecordType::A => Self::V4,
+            RecordType::Aaaa => Self::V6,
+            RecordType::Txt | RecordType::Cname => Self::Any,
+        }
+    }
+
+    pub fn matches(self, ip: &IpAddr) -> bool {
+        match self {
+            Self::V4 => ip.is_ipv4(),
+            Self::V6 => ip.is_ipv6(),
+            Self::Any => true,
+        }
+    }
+}
+
+#[async_trait]
+pub trait Resolver {
+    /// Resolves the host name to the first of its IP addresses in the address
+    /// family, if any.
+    async fn lookup(
+        &self,
+        host_name: &str,
+        family: AddressFamily,
+    ) -> Result<Option<String>, io::Error>;
+}
+
+/// Resolves host names through the system resolver.
+pub struct SystemResolver;
+
+#[async_trait]
+impl Resolver for SystemResolver {
+    async fn lookup(
+        &self,
+        host_name: &str,
+        family: AddressFamily,
+    ) -> Result<Option<String>, io::Error> {
+        // lookup_host resolves on the blocking pool, so a slow resolver
+        // doesn't stall the runtime
+        Ok(lookup_host((host_name, 80))
+            .await?
+            .map(|addr| addr.ip())
+            .find(|ip| family.matches(ip))
+            .map(|ip| ip.to_string()))
+    }
+}
+
+/// Resolves host names by querying a specific DNS server, bypassing any
+/// caching done by the system resolver.
+pub struct ServerResolver {
+    resolver: TokioAsyncResolver,
+}
+
+impl ServerResolver {
+    pub fn new(server: SocketAddr) -> Self {
+        let config = ResolverConfig::from_parts(
+            None,
+            Vec::new(),
+            NameServerConfigGroup::from_ips_clear(&[server.ip()], server.port(), true),
+        );
+        Self {
+            resolver: TokioAsyncResolver::tokio(config, ResolverOpts::default()),
+        }
+    }
+}
+
+#[async_trait]
+impl Resolver for ServerResolver {
+    async fn lookup(
+        &self,
+        host_name: &str,
+        family: AddressFamily,
+    ) -> Result<Option<String>, io::Error> {
+        // lookup_ip only asks for AAAA records when there are no A records,
+        // so query the family's record type directly
+        let result = match family {
+            AddressFamily::V4 => self
+                .resolver
+                .ipv4_lookup(host_name)
+                .await
+                .map(|ips| ips.iter().next().map(|ip| ip.to_string())),
+            AddressFamily::V6 => self
+                .resolver
+                .ipv6_lookup(host_name)
+                .await
+                .map(|ips| ips.iter().next().map(|ip| ip.to_string())),
+            AddressFamily::Any => self
+                .resolver
+                .lookup_ip(host_name)
+                .await
+                .map(|ips| ips.iter().next().map(|ip| ip.to_string())),
+        };
+        match result {
+            Ok(ip) => Ok(ip),
+            // A host with only an A record has no AAAA records, and vice versa
+            Err(e) if matches!(e.kind(), ResolveErrorKind::NoRecordsFound { .. }) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Returns the resolver querying the given DNS server, or the system
+/// resolver if none is given.
+pub fn resolver(server: Option<IpAddr>) -> Box<dyn Resolver> {
+    match server {
+        Some(server) => Box::new(ServerResolver::new(SocketAddr::new(server, 53))),
+        None => Box::new(SystemResolver),
+    }
+}
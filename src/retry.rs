@@ -0,0 +1,91 @@
+use crate::DNSUpdateError;
+use std::future::Future;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+use tracing::warn;
+
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+pub const DEFAULT_RETRY_BASE: Duration = Duration::from_millis(500);
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+pub struct Backoff {
+    max_retries: u32,
+    base: Duration,
+    max_delay: Duration,
+    /// How many retries a run may make across every operation, if limited
+    max_total_retries: Option<u32>,
+    /// How many retries the current run has made
+    total_retries: AtomicU32,
+}
+
+impl Backoff {
+    pub fn new(
+        max_retries: u32,
+        base: Duration,
+        max_delay: Duration,
+        max_total_retries: Option<u32>,
+    ) -> Self {
+        Self {
+            max_retries,
+            base,
+            max_delay,
+            max_total_retries,
+            total_retries: AtomicU32::new(0),
+        }
+    }
+
+    /// Returns the delay before the given retry, doubling with each attempt
+    /// up to the maximum delay.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.base
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay)
+    }
+
+    /// Gives the next run the full budget of retries.
+    pub fn reset_budget(&self) {
+        self.total_retries.store(0, Ordering::Relaxed);
+    }
+
+    /// Takes a retry from the run's budget, unless it's used up.
+    fn take_retry(&self) -> bool {
+        match self.max_total_retries {
+            Some(max) => self
+                .total_retries
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                    (used < max).then_some(used + 1)
+                })
+                .is_ok(),
+            None => true,
+        }
+    }
+
+    /// Runs the operation, retrying retryable failures with exponential backoff.
+    pub async fn retry<T, F, Fut>(&self, mut operation: F) -> Result<T, DNSUpdateError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, DNSUpdateError>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match operation().await {
+                Err(e) if e.is_retryable() && attempt < self.max_retries => {
+                    if !self.take_retry() {
+                        warn!("{e}; not retrying, the run used up MAX_TOTAL_RETRIES");
+                        return Err(e);
+                    }
+                    attempt += 1;
+                    let delay = self.delay(attempt);
+                    warn!(
+                        "{e}; retrying in {}ms ({attempt}/{})",
+                        delay.as_millis(),
+                        self.max_retries
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                result => return result,
+            }
+        }
+    }
+}
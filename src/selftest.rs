@@ -0,0 +1,98 @@
+use crate::config::{Args, Config, Target};
+use crate::providers::{build_updater, Provider, RecordType};
+use crate::{build_context, Context, DNSUpdateError};
+use std::fmt::Write;
+
+/// The name of a check and why it failed, if it did.
+pub type Check = (String, Result<(), String>);
+
+/// Checks that the configuration loads, that each IP source detects an
+/// address and that each provider accepts its credentials, without changing
+/// any records.
+pub async fn selftest(args: Args) -> Vec<Check> {
+    let mut checks = Vec::new();
+    let loaded = Config::load(args).and_then(|config| {
+        let context = build_context(&config)?;
+        Ok((config, context))
+    });
+    let (config, context) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            checks.push((String::from("Configuration"), Err(e.to_string())));
+            return checks;
+        }
+    };
+    checks.push((String::from("Configuration"), Ok(())));
+
+    for (record_type, source) in &context.sources {
+        // TXT and CNAME records carry a configured value, so nothing is
+        // detected
+        if record_type.is_some_and(|record_type| !record_type.is_address())
+            && config.record_value.is_some()
+        {
+            continue;
+        }
+        let result = source.detect_from().await.map(|_| ());
+        checks.push((
+            format!("IP detection from {source}"),
+            result.map_err(|e| e.to_string()),
+        ));
+    }
+
+    for target in &config.targets {
+        for provider in &target.providers {
+            let result = check_provider(&config, &context, target, *provider).await;
+            // Providers of [[records]] entries can each have their own
+            // credentials
+            let name = match target.host_names.first() {
+                Some(host_name) if config.targets.len() > 1 => {
+                    format!("{provider} credentials for {host_name}")
+                }
+                _ => format!("{provider} credentials"),
+            };
+            checks.push((name, result.map_err(|e| e.to_string())));
+        }
+    }
+    checks
+}
+
+/// Builds the provider's updater and, if its API can, reads the record of
+/// the first host of the target to make sure the credentials are accepted.
+async fn check_provider(
+    config: &Config,
+    context: &Context,
+    target: &Target,
+    provider: Provider,
+) -> Result<(), DNSUpdateError> {
+    let updater = build_updater(provider, &target.settings, &context.client).await?;
+    // Update-only APIs can't be called without changing the record
+    if !updater.can_read() {
+        return Ok(());
+    }
+    let Some(host_name) = target.host_names.first() else {
+        return Ok(());
+    };
+    let record_type = target
+        .record_type
+        .or_else(|| config.record_types.first().copied())
+        .unwrap_or(RecordType::A);
+    updater.current_record(host_name, record_type).await?;
+    Ok(())
+}
+
+/// Renders the checks as a checklist, one line each.
+pub fn checklist(checks: &[Check]) -> String {
+    let mut list = String::new();
+    for (name, result) in checks {
+        match result {
+            Ok(()) => writeln!(list, "[ok]   {name}").unwrap(),
+            Err(e) => writeln!(list, "[FAIL] {name}: {e}").unwrap(),
+        }
+    }
+    list
+}
+
+/// Returns the number of checks that failed.
+pub fn failures(checks: &[Check]) -> usize {
+    checks.iter().filter(|(_, result)| result.is_err()).count()
+}
@@ -0,0 +1,70 @@
+use super::{IpError, IpSource};
+use crate::resolver::{AddressFamily, Resolver, ServerResolver};
+use crate::DNSUpdateError;
+use async_trait::async_trait;
+use std::net::{IpAddr, SocketAddr};
+use std::{fmt, io};
+
+/// Detects the external IP address by asking a DNS service, e.g. "opendns",
+/// to resolve a special name to the address the query came from. The query
+/// goes over IPv6 to detect an IPv6 address.
+pub struct DnsSource {
+    service: String,
+    name: &'static str,
+    family: AddressFamily,
+    resolver: ServerResolver,
+}
+
+impl DnsSource {
+    pub fn new(service: &str, family: AddressFamily) -> Result<Self, DNSUpdateError> {
+        let (name, server) = match (service, family) {
+            ("opendns", AddressFamily::V6) => (
+                "myip.opendns.com",
+                IpAddr::from([0x2620, 0x119, 0x35, 0, 0, 0, 0, 0x35]),
+            ),
+            ("opendns", _) => ("myip.opendns.com", IpAddr::from([208, 67, 222, 222])),
+            _ => {
+                return Err(DNSUpdateError::Config(format!(
+                    "Unknown DNS IP source {service}"
+                )))
+            }
+        };
+        Ok(Self {
+            service: String::from(service),
+            name,
+            family,
+            resolver: ServerResolver::new(SocketAddr::new(server, 53)),
+        })
+    }
+
+    /// Asks the DNS server at the address rather than the service's, such as a
+    /// test server.
+    pub fn with_server(mut self, server: SocketAddr) -> Self {
+        self.resolver = ServerResolver::new(server);
+        self
+    }
+}
+
+impl fmt::Display for DnsSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "dns:{}", self.service)
+    }
+}
+
+#[async_trait]
+impl IpSource for DnsSource {
+    async fn detect(&self) -> Result<IpAddr, IpError> {
+        let ip = self
+            .resolver
+            .lookup(self.name, self.family)
+            .await
+            .map_err(IpError::Dns)?
+            .ok_or_else(|| {
+                IpError::Dns(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "no addresses found",
+                ))
+            })?;
+        ip.parse().map_err(|e| IpError::AddrParse(ip, e))
+    }
+}
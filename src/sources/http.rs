@@ -0,0 +1,76 @@
+use super::{IpError, IpSource};
+use crate::resolver::AddressFamily;
+use async_trait::async_trait;
+use reqwest::header::HeaderMap;
+use std::fmt;
+use std::net::IpAddr;
+
+#[async_trait]
+pub trait HttpFetcher: Send + Sync {
+    /// Fetches the body of the URL as text with the extra headers, failing
+    /// unless the response is successful.
+    async fn get_text(&self, url: &str, headers: &HeaderMap) -> Result<String, IpError>;
+}
+
+#[async_trait]
+impl HttpFetcher for reqwest::Client {
+    async fn get_text(&self, url: &str, headers: &HeaderMap) -> Result<String, IpError> {
+        Ok(self
+            .get(url)
+            .header("Accept", "text/plain")
+            // Replaces the Accept header if one is given
+            .headers(headers.clone())
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?)
+    }
+}
+
+/// Detects the external IP address by fetching it from a service that echoes
+/// the address of the client as plain text. The fetcher is expected to connect
+/// over the protocol of the family, so the service sees an address of it.
+pub struct HttpSource {
+    fetcher: Box<dyn HttpFetcher>,
+    url: String,
+    family: AddressFamily,
+    headers: HeaderMap,
+}
+
+impl HttpSource {
+    pub fn new(
+        fetcher: Box<dyn HttpFetcher>,
+        url: &str,
+        family: AddressFamily,
+        headers: HeaderMap,
+    ) -> Self {
+        Self {
+            fetcher,
+            url: String::from(url),
+            family,
+            headers,
+        }
+    }
+}
+
+impl fmt::Display for HttpSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.url)
+    }
+}
+
+#[async_trait]
+impl IpSource for HttpSource {
+    async fn detect(&self) -> Result<IpAddr, IpError> {
+        let body = self.fetcher.get_text(&self.url, &self.headers).await?;
+        let body = body.trim();
+        let ip = body
+            .parse()
+            .map_err(|e| IpError::AddrParse(String::from(body), e))?;
+        if !self.family.matches(&ip) {
+            return Err(IpError::Family(ip));
+        }
+        Ok(ip)
+    }
+}
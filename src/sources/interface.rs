@@ -0,0 +1,74 @@
+use super::{IpError, IpSource};
+use crate::resolver::AddressFamily;
+use async_trait::async_trait;
+use std::net::IpAddr;
+use std::{fmt, io};
+
+/// Returns whether the address is routable on the internet, i.e. isn't
+/// loopback, link-local, private or otherwise reserved.
+pub fn is_global(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_private()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                // Shared address space used by carrier-grade NAT
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => {
+            let segment = ip.segments()[0];
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                // Unique local addresses
+                || (segment & 0xfe00) == 0xfc00
+                // Link-local unicast addresses
+                || (segment & 0xffc0) == 0xfe80
+                // Documentation addresses
+                || segment == 0x2001 && ip.segments()[1] == 0x0db8)
+        }
+    }
+}
+
+/// Detects the external IP address as the first global address of the family
+/// assigned to the network interface.
+pub struct InterfaceSource {
+    name: String,
+    family: AddressFamily,
+}
+
+impl InterfaceSource {
+    pub fn new(name: &str, family: AddressFamily) -> Self {
+        Self {
+            name: String::from(name),
+            family,
+        }
+    }
+}
+
+impl fmt::Display for InterfaceSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "interface:{}", self.name)
+    }
+}
+
+#[async_trait]
+impl IpSource for InterfaceSource {
+    async fn detect(&self) -> Result<IpAddr, IpError> {
+        if_addrs::get_if_addrs()
+            .map_err(IpError::Interface)?
+            .iter()
+            .filter(|interface| interface.name == self.name)
+            .map(|interface| interface.ip())
+            .find(|ip| self.family.matches(ip) && is_global(ip))
+            .ok_or_else(|| {
+                IpError::Interface(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no global addresses on interface {}", self.name),
+                ))
+            })
+    }
+}
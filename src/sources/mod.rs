@@ -0,0 +1,256 @@
+use crate::resolver::AddressFamily;
+use crate::DNSUpdateError;
+use async_trait::async_trait;
+use reqwest::header::HeaderMap;
+use std::net::{IpAddr, Ipv6Addr};
+use std::time::Duration;
+use std::{error, fmt, io, net};
+
+mod dns;
+mod http;
+mod interface;
+mod stun;
+mod upnp;
+
+pub use dns::DnsSource;
+pub use http::{HttpFetcher, HttpSource};
+pub use interface::{is_global, InterfaceSource};
+pub use stun::StunSource;
+pub use upnp::{IgdGateway, UpnpGateway, UpnpSource};
+
+#[derive(Debug)]
+pub enum IpError {
+    Http(reqwest::Error),
+    Dns(io::Error),
+    Interface(io::Error),
+    Stun(io::Error),
+    Upnp(io::Error),
+    AddrParse(String, net::AddrParseError),
+    /// The configured address is of the wrong family
+    Fixed(IpAddr),
+    /// The service answered with an address of the wrong family, as when it
+    /// was reached through a proxy
+    Family(IpAddr),
+    /// Every source of a fallback failed, with the spelling of each source
+    AllFailed(Vec<(String, IpError)>),
+}
+
+impl fmt::Display for IpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Http(e) => write!(f, "{e}"),
+            Self::Dns(e) => write!(f, "{e}"),
+            Self::Interface(e) => write!(f, "{e}"),
+            Self::Stun(e) => write!(f, "{e}"),
+            Self::Upnp(e) => write!(f, "{e}"),
+            Self::AddrParse(body, e) => write!(f, "invalid IP address {body:?}: {e}"),
+            Self::Fixed(ip) => write!(f, "configured IP address {ip} is of the wrong family"),
+            Self::Family(ip) => write!(f, "detected IP address {ip} is of the wrong family"),
+            Self::AllFailed(errors) => {
+                for (i, (source, e)) in errors.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{source}: {e}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl error::Error for IpError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Http(e) => Some(e),
+            Self::Dns(e) => Some(e),
+            Self::Interface(e) => Some(e),
+            Self::Stun(e) => Some(e),
+            Self::Upnp(e) => Some(e),
+            Self::AddrParse(_, e) => Some(e),
+            Self::Fixed(_) | Self::Family(_) => None,
+            Self::AllFailed(errors) => errors.last().map(|(_, e)| e as _),
+        }
+    }
+}
+
+impl From<reqwest::Error> for IpError {
+    fn from(e: reqwest::Error) -> Self {
+        Self::Http(e)
+    }
+}
+
+/// A way of detecting the external IP address. Displays as it's spelled in
+/// IP_SOURCES.
+#[async_trait]
+pub trait IpSource: fmt::Display + Send + Sync {
+    async fn detect(&self) -> Result<IpAddr, IpError>;
+
+    /// Detects the address along with the spelling of the source that
+    /// detected it.
+    async fn detect_from(&self) -> Result<(IpAddr, String), IpError> {
+        self.detect().await.map(|ip| (ip, self.to_string()))
+    }
+}
+
+/// Returns a configured address rather than detecting one, as long as it's of
+/// the family.
+pub struct FixedSource {
+    ip: IpAddr,
+    family: AddressFamily,
+}
+
+impl FixedSource {
+    pub fn new(ip: IpAddr, family: AddressFamily) -> Self {
+        Self { ip, family }
+    }
+}
+
+impl fmt::Display for FixedSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "EXTERNAL_IP={}", self.ip)
+    }
+}
+
+#[async_trait]
+impl IpSource for FixedSource {
+    async fn detect(&self) -> Result<IpAddr, IpError> {
+        if self.family.matches(&self.ip) {
+            Ok(self.ip)
+        } else {
+            Err(IpError::Fixed(self.ip))
+        }
+    }
+}
+
+/// Tries each source in order, returning the first address detected.
+pub struct FallbackSource {
+    sources: Vec<Box<dyn IpSource>>,
+}
+
+impl FallbackSource {
+    pub fn new(sources: Vec<Box<dyn IpSource>>) -> Self {
+        Self { sources }
+    }
+}
+
+impl fmt::Display for FallbackSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, source) in self.sources.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{source}")?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl IpSource for FallbackSource {
+    async fn detect(&self) -> Result<IpAddr, IpError> {
+        self.detect_from().await.map(|(ip, _)| ip)
+    }
+
+    async fn detect_from(&self) -> Result<(IpAddr, String), IpError> {
+        let mut errors = Vec::new();
+        for source in &self.sources {
+            match source.detect_from().await {
+                Ok(detected) => return Ok(detected),
+                Err(e) => errors.push((source.to_string(), e)),
+            }
+        }
+        Err(IpError::AllFailed(errors))
+    }
+}
+
+/// Returns the address made of the first prefix_length bits of the prefix and
+/// the remaining bits of the suffix.
+pub fn combine_prefix_suffix(prefix: Ipv6Addr, prefix_length: u8, suffix: Ipv6Addr) -> Ipv6Addr {
+    let mask = u128::MAX
+        .checked_shl(128 - u32::from(prefix_length.min(128)))
+        .unwrap_or(0);
+    Ipv6Addr::from(u128::from(prefix) & mask | u128::from(suffix) & !mask)
+}
+
+/// Combines the prefix of detected IPv6 addresses with a configured suffix,
+/// for hosts whose interface identifier is stable while the delegated prefix
+/// rotates. IPv4 addresses are returned as detected.
+pub struct SuffixSource {
+    source: Box<dyn IpSource>,
+    prefix_length: u8,
+    suffix: Ipv6Addr,
+}
+
+impl SuffixSource {
+    pub fn new(source: Box<dyn IpSource>, prefix_length: u8, suffix: Ipv6Addr) -> Self {
+        Self {
+            source,
+            prefix_length,
+            suffix,
+        }
+    }
+
+    fn combine(&self, ip: IpAddr) -> IpAddr {
+        match ip {
+            IpAddr::V4(_) => ip,
+            IpAddr::V6(prefix) => IpAddr::V6(combine_prefix_suffix(
+                prefix,
+                self.prefix_length,
+                self.suffix,
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SuffixSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+#[async_trait]
+impl IpSource for SuffixSource {
+    async fn detect(&self) -> Result<IpAddr, IpError> {
+        self.source.detect().await.map(|ip| self.combine(ip))
+    }
+
+    async fn detect_from(&self) -> Result<(IpAddr, String), IpError> {
+        let (ip, source) = self.source.detect_from().await?;
+        Ok((self.combine(ip), source))
+    }
+}
+
+/// Builds the source for the configured IP_SOURCES, detecting addresses of
+/// the family where the source allows choosing. HTTP sources are sent the
+/// extra headers.
+pub fn ip_source(
+    sources: &[String],
+    client: &reqwest::Client,
+    headers: &HeaderMap,
+    family: AddressFamily,
+    timeout: Duration,
+) -> Result<FallbackSource, DNSUpdateError> {
+    let sources = sources
+        .iter()
+        .map(|source| -> Result<Box<dyn IpSource>, DNSUpdateError> {
+            Ok(if let Some(service) = source.strip_prefix("dns:") {
+                Box::new(DnsSource::new(service, family)?)
+            } else if let Some(name) = source.strip_prefix("interface:") {
+                Box::new(InterfaceSource::new(name, family))
+            } else if let Some(server) = source.strip_prefix("stun:") {
+                Box::new(StunSource::new(server, family, timeout))
+            } else if source == "upnp" {
+                Box::new(UpnpSource::new(Box::new(IgdGateway::new(timeout)?), family))
+            } else {
+                Box::new(HttpSource::new(
+                    Box::new(client.clone()),
+                    source,
+                    family,
+                    headers.clone(),
+                ))
+            })
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(FallbackSource::new(sources))
+}
@@ -0,0 +1,155 @@
+use super::{IpError, IpSource};
+use crate::resolver::AddressFamily;
+use async_trait::async_trait;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+use std::{fmt, io};
+use tokio::net::{lookup_host, UdpSocket};
+
+const BINDING_REQUEST: u16 = 0x0001;
+const BINDING_SUCCESS: u16 = 0x0101;
+const MAGIC_COOKIE: [u8; 4] = [0x21, 0x12, 0xa4, 0x42];
+const MAPPED_ADDRESS: u16 = 0x0001;
+const XOR_MAPPED_ADDRESS: u16 = 0x0020;
+const HEADER_LEN: usize = 20;
+
+fn malformed(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Returns a transaction ID; it only needs to be unpredictable enough to match
+/// the response to the request.
+fn transaction_id() -> [u8; 12] {
+    let mut id = [0; 12];
+    let random = RandomState::new();
+    id[..8].copy_from_slice(&random.build_hasher().finish().to_be_bytes());
+    let mut hasher = random.build_hasher();
+    hasher.write_u8(1);
+    id[8..].copy_from_slice(&hasher.finish().to_be_bytes()[..4]);
+    id
+}
+
+fn binding_request(transaction_id: &[u8; 12]) -> Vec<u8> {
+    let mut request = Vec::with_capacity(HEADER_LEN);
+    request.extend_from_slice(&BINDING_REQUEST.to_be_bytes());
+    request.extend_from_slice(&0u16.to_be_bytes());
+    request.extend_from_slice(&MAGIC_COOKIE);
+    request.extend_from_slice(transaction_id);
+    request
+}
+
+/// Parses the address of a (XOR-)MAPPED-ADDRESS attribute value, unmasking
+/// it with the mask.
+fn mapped_address(value: &[u8], mask: &[u8]) -> io::Result<IpAddr> {
+    let address: Vec<u8> = value
+        .get(4..)
+        .unwrap_or_default()
+        .iter()
+        .enumerate()
+        .map(|(i, byte)| byte ^ mask.get(i).copied().unwrap_or_default())
+        .collect();
+    match value.get(1) {
+        Some(1) => <[u8; 4]>::try_from(address.as_slice())
+            .map(IpAddr::from)
+            .map_err(|_| malformed("invalid IPv4 address")),
+        Some(2) => <[u8; 16]>::try_from(address.as_slice())
+            .map(IpAddr::from)
+            .map_err(|_| malformed("invalid IPv6 address")),
+        _ => Err(malformed("unknown address family")),
+    }
+}
+
+/// Parses the mapped address out of a binding success response.
+fn parse_response(response: &[u8], transaction_id: &[u8; 12]) -> io::Result<IpAddr> {
+    if response.len() < HEADER_LEN
+        || response[..2] != BINDING_SUCCESS.to_be_bytes()
+        || response[4..8] != MAGIC_COOKIE
+        || response[8..HEADER_LEN] != transaction_id[..]
+    {
+        return Err(malformed("not a binding response to the request"));
+    }
+
+    // XOR-MAPPED-ADDRESS is masked by the cookie and transaction ID, while the
+    // older MAPPED-ADDRESS is sent as is
+    let xor_mask: Vec<u8> = MAGIC_COOKIE.iter().chain(transaction_id).copied().collect();
+    let mut mapped = None;
+    let mut attributes = &response[HEADER_LEN..];
+    while attributes.len() >= 4 {
+        let kind = u16::from_be_bytes([attributes[0], attributes[1]]);
+        let len = usize::from(u16::from_be_bytes([attributes[2], attributes[3]]));
+        let value = attributes
+            .get(4..4 + len)
+            .ok_or_else(|| malformed("truncated attribute"))?;
+        match kind {
+            XOR_MAPPED_ADDRESS => return mapped_address(value, &xor_mask),
+            MAPPED_ADDRESS => mapped = Some(mapped_address(value, &[])?),
+            _ => {}
+        }
+        // Attributes are padded to a multiple of four bytes
+        attributes = attributes
+            .get(4 + len.div_ceil(4) * 4..)
+            .unwrap_or_default();
+    }
+    mapped.ok_or_else(|| malformed("no mapped address in response"))
+}
+
+/// Detects the external IP address by sending a STUN binding request to the
+/// server, given as host:port, and reading back the address it saw.
+async fn stun_binding(
+    server: &str,
+    family: AddressFamily,
+    timeout: Duration,
+) -> io::Result<IpAddr> {
+    let server = lookup_host(server)
+        .await?
+        .find(|addr| family.matches(&addr.ip()))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no addresses found"))?;
+    let local = match server {
+        SocketAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+        SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect(server).await?;
+
+    let transaction_id = transaction_id();
+    socket.send(&binding_request(&transaction_id)).await?;
+    let mut response = [0; 576];
+    let len = tokio::time::timeout(timeout, socket.recv(&mut response))
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "no response from server"))??;
+    parse_response(&response[..len], &transaction_id)
+}
+
+/// Detects the external IP address with a binding request to a STUN server.
+pub struct StunSource {
+    server: String,
+    family: AddressFamily,
+    timeout: Duration,
+}
+
+impl StunSource {
+    pub fn new(server: &str, family: AddressFamily, timeout: Duration) -> Self {
+        Self {
+            server: String::from(server),
+            family,
+            timeout,
+        }
+    }
+}
+
+impl fmt::Display for StunSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "stun:{}", self.server)
+    }
+}
+
+#[async_trait]
+impl IpSource for StunSource {
+    async fn detect(&self) -> Result<IpAddr, IpError> {
+        stun_binding(&self.server, self.family, self.timeout)
+            .await
+            .map_err(IpError::Stun)
+    }
+}
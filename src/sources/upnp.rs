@@ -0,0 +1,183 @@
+use super::{IpError, IpSource};
+use crate::resolver::AddressFamily;
+use crate::xml::element;
+use crate::{DNSUpdateError, USER_AGENT};
+use async_trait::async_trait;
+use reqwest::Url;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+use std::{fmt, io};
+use tokio::net::UdpSocket;
+
+const SSDP_ADDRESS: &str = "239.255.255.250:1900";
+const GATEWAY_TYPE: &str = "urn:schemas-upnp-org:device:InternetGatewayDevice:1";
+/// The services of a gateway that report its external address
+const SERVICE_TYPES: [&str; 3] = [
+    "urn:schemas-upnp-org:service:WANIPConnection:1",
+    "urn:schemas-upnp-org:service:WANIPConnection:2",
+    "urn:schemas-upnp-org:service:WANPPPConnection:1",
+];
+
+fn unsupported(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, message)
+}
+
+/// Finds the gateway on the local network with an SSDP search, returning the
+/// URL of its device description.
+async fn discover_gateway(timeout: Duration) -> io::Result<Url> {
+    let socket = UdpSocket::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))).await?;
+    let search = format!(
+        "M-SEARCH * HTTP/1.1\r\nHOST: {SSDP_ADDRESS}\r\nST: {GATEWAY_TYPE}\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\n\r\n"
+    );
+    socket.send_to(search.as_bytes(), SSDP_ADDRESS).await?;
+
+    let receive = async {
+        let mut response = [0; 1500];
+        loop {
+            let (len, _) = socket.recv_from(&mut response).await?;
+            // Other devices may answer too, so only a response naming its
+            // description is of use
+            let location = String::from_utf8_lossy(&response[..len])
+                .lines()
+                .find_map(|line| {
+                    let (name, value) = line.split_once(':')?;
+                    name.eq_ignore_ascii_case("location")
+                        .then(|| Url::parse(value.trim()).ok())
+                        .flatten()
+                });
+            if let Some(location) = location {
+                return Ok(location);
+            }
+        }
+    };
+    tokio::time::timeout(timeout, receive)
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "no UPnP gateway found"))?
+}
+
+/// Returns the control URL and type of the gateway's first service that
+/// reports the external address.
+fn connection_service(description: &str, location: &Url) -> io::Result<(Url, &'static str)> {
+    for service in description.split("<service>").skip(1) {
+        let Some(service_type) = element(service, "serviceType")
+            .and_then(|service_type| SERVICE_TYPES.into_iter().find(|t| *t == service_type))
+        else {
+            continue;
+        };
+        let control_url = element(service, "controlURL")
+            .and_then(|url| location.join(url).ok())
+            .ok_or_else(|| unsupported("gateway service has no control URL"))?;
+        return Ok((control_url, service_type));
+    }
+    Err(unsupported(
+        "gateway doesn't report its external IP address",
+    ))
+}
+
+#[async_trait]
+pub trait UpnpGateway: Send + Sync {
+    /// Asks the gateway for its external IP address, returning the body of
+    /// its GetExternalIPAddress SOAP response.
+    async fn get_external_ip_address(&self) -> Result<String, IpError>;
+}
+
+/// The Internet Gateway Device on the local network, found by SSDP search.
+pub struct IgdGateway {
+    /// A client that never goes through a proxy, as the gateway is local
+    client: reqwest::Client,
+    timeout: Duration,
+}
+
+impl IgdGateway {
+    pub fn new(timeout: Duration) -> Result<Self, DNSUpdateError> {
+        let client = reqwest::Client::builder()
+            .no_proxy()
+            .timeout(timeout)
+            .user_agent(USER_AGENT)
+            .build()
+            .map_err(|e| DNSUpdateError::Config(format!("Unable to create HTTP client: {e}")))?;
+        Ok(Self { client, timeout })
+    }
+}
+
+#[async_trait]
+impl UpnpGateway for IgdGateway {
+    async fn get_external_ip_address(&self) -> Result<String, IpError> {
+        let location = discover_gateway(self.timeout)
+            .await
+            .map_err(IpError::Upnp)?;
+        let description = self
+            .client
+            .get(location.clone())
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let (control_url, service_type) =
+            connection_service(&description, &location).map_err(IpError::Upnp)?;
+
+        let body = format!(
+            "<?xml version=\"1.0\"?>\
+            <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" \
+            s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\">\
+            <s:Body><u:GetExternalIPAddress xmlns:u=\"{service_type}\"/></s:Body>\
+            </s:Envelope>"
+        );
+        Ok(self
+            .client
+            .post(control_url)
+            .header("Content-Type", "text/xml; charset=\"utf-8\"")
+            .header(
+                "SOAPAction",
+                format!("\"{service_type}#GetExternalIPAddress\""),
+            )
+            .body(body)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?)
+    }
+}
+
+/// Detects the external IP address by asking the router, which has to be an
+/// Internet Gateway Device speaking UPnP. Gateways only report their IPv4
+/// address.
+pub struct UpnpSource {
+    gateway: Box<dyn UpnpGateway>,
+    family: AddressFamily,
+}
+
+impl UpnpSource {
+    pub fn new(gateway: Box<dyn UpnpGateway>, family: AddressFamily) -> Self {
+        Self { gateway, family }
+    }
+}
+
+impl fmt::Display for UpnpSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "upnp")
+    }
+}
+
+#[async_trait]
+impl IpSource for UpnpSource {
+    async fn detect(&self) -> Result<IpAddr, IpError> {
+        if self.family == AddressFamily::V6 {
+            return Err(IpError::Upnp(unsupported(
+                "UPnP gateways only report IPv4 addresses",
+            )));
+        }
+        let response = self.gateway.get_external_ip_address().await?;
+        // Gateways without a connection answer with an empty address
+        let address = element(&response, "NewExternalIPAddress").unwrap_or_default();
+        let ip = address
+            .parse()
+            .map_err(|e| IpError::AddrParse(String::from(address), e))?;
+        if !self.family.matches(&ip) {
+            return Err(IpError::Family(ip));
+        }
+        Ok(ip)
+    }
+}
@@ -0,0 +1,189 @@
+use crate::providers::{Provider, RecordType};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{fs, io};
+use tracing::warn;
+
+pub const DEFAULT_STATE_FILE: &str = "/tmp/dyndns-state";
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// A record value pushed to the providers, remembered while it propagates.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SetRecord {
+    pub host_name: String,
+    pub record_type: RecordType,
+    pub value: String,
+    /// Seconds since the Unix epoch
+    pub set_at: u64,
+}
+
+impl SetRecord {
+    pub fn new(host_name: &str, record_type: RecordType, value: &str) -> Self {
+        Self {
+            host_name: String::from(host_name),
+            record_type,
+            value: String::from(value),
+            set_at: now(),
+        }
+    }
+}
+
+/// An address pushed to a host at a provider.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PushedRecord {
+    pub host_name: String,
+    /// The name of the provider in the PROVIDER setting
+    pub provider: String,
+    pub record_type: RecordType,
+    pub value: String,
+}
+
+impl PushedRecord {
+    pub fn new(host_name: &str, provider: Provider, record_type: RecordType, value: &str) -> Self {
+        Self {
+            host_name: String::from(host_name),
+            provider: String::from(provider.as_str()),
+            record_type,
+            value: String::from(value),
+        }
+    }
+}
+
+/// A changed external IP address waiting to be stable for long enough.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PendingIp {
+    pub ip: IpAddr,
+    /// Seconds since the Unix epoch
+    pub first_seen: u64,
+}
+
+/// The external IP addresses last successfully pushed, one per record type,
+/// the hosts and providers they were pushed to, and the records recently set.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct State {
+    pub ips: Vec<IpAddr>,
+    /// Seconds since the Unix epoch
+    pub updated_at: u64,
+    #[serde(default)]
+    pub records: Vec<SetRecord>,
+    #[serde(default)]
+    pub pending: Vec<PendingIp>,
+    #[serde(default)]
+    pub pushed: Vec<PushedRecord>,
+}
+
+impl State {
+    /// Records the addresses as pushed to the hosts at their providers,
+    /// forgetting where older addresses were pushed.
+    pub fn set_ips(&mut self, ips: Vec<IpAddr>, pushed: Vec<PushedRecord>) {
+        self.pending.retain(|pending| !ips.contains(&pending.ip));
+        self.pushed.retain(|record| {
+            ips.iter().any(|ip| ip.to_string() == record.value)
+                && !pushed.iter().any(|new| {
+                    new.host_name == record.host_name
+                        && new.provider == record.provider
+                        && new.record_type == record.record_type
+                })
+        });
+        self.pushed.extend(pushed);
+        if self.ips != ips {
+            self.ips = ips;
+            self.updated_at = now();
+        }
+    }
+
+    /// Returns whether the address was pushed to the host at the provider.
+    pub fn was_pushed(&self, host_name: &str, provider: Provider, ip: IpAddr) -> bool {
+        let value = ip.to_string();
+        self.pushed.iter().any(|record| {
+            record.host_name == host_name
+                && record.provider == provider.as_str()
+                && record.record_type == RecordType::for_address(ip)
+                && record.value == value
+        })
+    }
+
+    /// Notes the address as detected, returning how long it's been detected
+    /// on every run. Seeing another address of the family starts over.
+    pub fn observe(&mut self, ip: IpAddr) -> Duration {
+        let now = now();
+        self.pending
+            .retain(|pending| pending.ip == ip || pending.ip.is_ipv4() != ip.is_ipv4());
+        match self.pending.iter().find(|pending| pending.ip == ip) {
+            Some(pending) => Duration::from_secs(now.saturating_sub(pending.first_seen)),
+            None => {
+                self.pending.push(PendingIp {
+                    ip,
+                    first_seen: now,
+                });
+                Duration::ZERO
+            }
+        }
+    }
+
+    /// Drops any pending address of the address's family, returning whether
+    /// there was one.
+    pub fn forget_pending(&mut self, ip: IpAddr) -> bool {
+        let count = self.pending.len();
+        self.pending
+            .retain(|pending| pending.ip.is_ipv4() != ip.is_ipv4());
+        self.pending.len() != count
+    }
+
+    /// Remembers the records, forgetting those set longer than the grace
+    /// period ago.
+    pub fn remember(&mut self, records: Vec<SetRecord>, grace: Duration) {
+        let now = now();
+        self.records.retain(|record| {
+            now.saturating_sub(record.set_at) < grace.as_secs()
+                && !records.iter().any(|new| {
+                    new.host_name == record.host_name && new.record_type == record.record_type
+                })
+        });
+        self.records.extend(records);
+    }
+
+    /// Returns whether the record was set to the value within the grace
+    /// period, so resolvers may not see it yet.
+    pub fn recently_set(
+        &self,
+        host_name: &str,
+        record_type: RecordType,
+        value: &str,
+        grace: Duration,
+    ) -> bool {
+        let now = now();
+        self.records.iter().any(|record| {
+            record.host_name == host_name
+                && record.record_type == record_type
+                && record.value == value
+                && now.saturating_sub(record.set_at) < grace.as_secs()
+        })
+    }
+}
+
+/// Loads the saved state, treating a missing or unreadable file as unknown.
+pub fn load_state(path: &Path) -> Option<State> {
+    let contents = match fs::read(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            warn!("Unable to read state file {}: {e}", path.display());
+            return None;
+        }
+    };
+    serde_json::from_slice(&contents)
+        .map_err(|e| warn!("Ignoring corrupt state file {}: {e}", path.display()))
+        .ok()
+}
+
+pub fn save_state(path: &Path, state: &State) -> io::Result<()> {
+    fs::write(path, serde_json::to_vec(state)?)
+}
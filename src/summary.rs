@@ -0,0 +1,114 @@
+use crate::providers::{Provider, RecordType};
+use crate::webhook::Event;
+use crate::DNSUpdateError;
+use serde::Serialize;
+use std::fmt::Write;
+use std::net::IpAddr;
+
+/// The outcome of a run, printed in JSON output mode.
+#[derive(Debug, Default, Serialize)]
+pub struct RunSummary {
+    pub external_ips: Vec<IpAddr>,
+    pub records: Vec<RecordSummary>,
+    pub hosts: Vec<HostResult>,
+    pub error: Option<String>,
+}
+
+impl RunSummary {
+    /// Whether every record was found up to date. Records a dry run would
+    /// have updated count as changes, so that QUIET still reports them.
+    pub fn unchanged(&self) -> bool {
+        self.hosts
+            .iter()
+            .all(|host| matches!(host.status, HostStatus::Unchanged))
+    }
+
+    /// Renders the host results as a table for text output.
+    pub fn host_table(&self) -> String {
+        let width = self
+            .hosts
+            .iter()
+            .map(|host| host.host_name.len())
+            .chain([4])
+            .max()
+            .unwrap_or_default();
+        let mut table = format!("{:width$}  TYPE  STATUS\n", "HOST");
+        for host in &self.hosts {
+            let status = match &host.status {
+                HostStatus::Updated => String::from("updated"),
+                HostStatus::WouldUpdate => String::from("would update"),
+                HostStatus::Unchanged => String::from("unchanged"),
+                HostStatus::RateLimited => String::from("rate limited"),
+                HostStatus::Drifted => String::from("out of sync"),
+                HostStatus::Failed(error) => format!("failed: {error}"),
+            };
+            writeln!(
+                table,
+                "{:width$}  {:4}  {status}",
+                host.host_name,
+                host.record_type.as_str()
+            )
+            .unwrap();
+        }
+        table
+    }
+}
+
+/// What became of the record of a host across every provider.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case", tag = "status", content = "error")]
+pub enum HostStatus {
+    Updated,
+    /// Found stale at some provider in a dry run
+    WouldUpdate,
+    Unchanged,
+    /// Left stale at some provider to respect MIN_UPDATE_INTERVAL
+    RateLimited,
+    /// Found stale at some provider in check mode
+    Drifted,
+    Failed(String),
+}
+
+/// The outcome for the record of one host.
+#[derive(Debug, Serialize)]
+pub struct HostResult {
+    pub host_name: String,
+    pub record_type: RecordType,
+    #[serde(flatten)]
+    pub status: HostStatus,
+}
+
+/// The outcome for the record of one host at one provider.
+#[derive(Debug, Serialize)]
+pub struct RecordSummary {
+    pub provider: String,
+    pub host_name: String,
+    pub previous_ip: Option<String>,
+    pub changed: bool,
+    pub error: Option<String>,
+}
+
+impl RecordSummary {
+    /// Summarizes a record that couldn't be compared or updated.
+    pub fn failed(host_name: &str, provider: &Provider, error: &DNSUpdateError) -> Self {
+        Self {
+            provider: provider.to_string(),
+            host_name: String::from(host_name),
+            previous_ip: None,
+            changed: false,
+            error: Some(error.to_string()),
+        }
+    }
+}
+
+impl From<&Event> for RecordSummary {
+    fn from(event: &Event) -> Self {
+        Self {
+            provider: event.provider.clone(),
+            host_name: event.host_name.clone(),
+            previous_ip: event.old_ip.clone(),
+            changed: event.error.is_none() && event.changed(),
+            error: event.error.clone(),
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use serde::Serialize;
+use std::str;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WebhookOn {
+    /// Notify when a record is updated
+    Change,
+    /// Notify about every record checked, updated or not
+    Always,
+    /// Notify when a record fails to update
+    Error,
+}
+
+impl WebhookOn {
+    pub fn wants(self, event: &Event) -> bool {
+        match self {
+            Self::Change => event.error.is_none() && event.changed(),
+            Self::Always => true,
+            Self::Error => event.error.is_some(),
+        }
+    }
+}
+
+impl str::FromStr for WebhookOn {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "change" => Ok(Self::Change),
+            "always" => Ok(Self::Always),
+            "error" => Ok(Self::Error),
+            _ => Err(format!("Unknown webhook mode {s}")),
+        }
+    }
+}
+
+/// The outcome of updating the record of a host at one provider.
+#[derive(Debug, Serialize)]
+pub struct Event {
+    pub host_name: String,
+    pub old_ip: Option<String>,
+    pub new_ip: String,
+    pub provider: String,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Event {
+    pub fn new(
+        host_name: &str,
+        provider: impl ToString,
+        old_ip: Option<&str>,
+        new_ip: &str,
+    ) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        Self {
+            host_name: String::from(host_name),
+            old_ip: old_ip.map(String::from),
+            new_ip: String::from(new_ip),
+            provider: provider.to_string(),
+            timestamp,
+            error: None,
+        }
+    }
+
+    pub fn changed(&self) -> bool {
+        self.old_ip.as_ref() != Some(&self.new_ip)
+    }
+}
+
+/// Posts the event to the webhook. Delivery failures are only logged, since
+/// they don't affect the records themselves.
+pub async fn notify_webhook(client: &reqwest::Client, url: &str, event: &Event) {
+    let result = client
+        .post(url)
+        .json(event)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status());
+    match result {
+        Ok(_) => debug!(url, "Delivered webhook"),
+        Err(e) => warn!("Unable to deliver webhook to {url}: {e}"),
+    }
+}
@@ -0,0 +1,7 @@
+/// Returns the text of the first element with the name in the XML. The
+/// elements read this way never have attributes.
+pub fn element<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{name}>"))? + name.len() + 2;
+    let end = start + xml[start..].find(&format!("</{name}>"))?;
+    Some(xml[start..end].trim())
+}
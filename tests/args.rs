@@ -0,0 +1,55 @@
+//! Parses the command line, falling back to env vars for omitted options.
+
+use clap::Parser;
+use dyndns_rs::providers::Provider;
+use dyndns_rs::Args;
+use std::env;
+
+#[test]
+fn parses_command_line() {
+    let args = Args::try_parse_from([
+        "dyndns-rs",
+        "--provider",
+        "route53,duckdns",
+        "--host-name",
+        "home.example.com",
+        "--dry-run",
+        "--ttl",
+        "60",
+        "--set",
+        "HOSTED_ZONE_ID=Z123",
+    ])
+    .unwrap();
+
+    assert_eq!(args.provider, [Provider::Route53, Provider::DuckDns]);
+    assert_eq!(args.host_name, ["home.example.com"]);
+    assert_eq!(args.dry_run, Some(true));
+    assert_eq!(args.ttl, Some(60));
+    assert_eq!(
+        args.overrides,
+        [(String::from("HOSTED_ZONE_ID"), String::from("Z123"))]
+    );
+}
+
+#[test]
+fn fills_omitted_option_from_env_var() {
+    // The other test gives every option this one reads
+    env::set_var("HOST_NAME", "home.example.com,other.example.com");
+    let args = Args::try_parse_from(["dyndns-rs", "--ttl", "60"]);
+    env::remove_var("HOST_NAME");
+
+    assert_eq!(
+        args.unwrap().host_name,
+        ["home.example.com", "other.example.com"]
+    );
+}
+
+#[test]
+fn rejects_unknown_provider() {
+    let e = Args::try_parse_from(["dyndns-rs", "--provider", "cloudflare"]).unwrap_err();
+
+    assert!(
+        e.to_string().contains("Unknown provider 'cloudflare'"),
+        "{e}"
+    );
+}
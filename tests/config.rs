@@ -0,0 +1,275 @@
+//! Loads the configuration from a TOML config file.
+
+use clap::Parser;
+use dyndns_rs::config::Config;
+use dyndns_rs::providers::Provider;
+use dyndns_rs::{Args, DNSUpdateError};
+use std::env;
+use std::time::Duration;
+
+/// Loads a config file with the contents, given no other settings.
+fn load(name: &str, contents: &str) -> Result<Config, DNSUpdateError> {
+    let path = env::temp_dir().join(format!("dyndns-rs-test-{name}-{}.toml", std::process::id()));
+    std::fs::write(&path, contents).unwrap();
+    let config = Config::load(Args::parse_from([
+        String::from("dyndns-rs"),
+        format!("--config={}", path.display()),
+    ]));
+    let _ = std::fs::remove_file(path);
+    config
+}
+
+#[test]
+fn interpolates_environment_variables() {
+    env::set_var("DYNDNS_RS_TEST_DOMAIN", "example.com");
+    let config = load(
+        "interpolated",
+        "host_name = \"home.${DYNDNS_RS_TEST_DOMAIN}\"\nprovider = \"route53\"\n",
+    )
+    .unwrap();
+
+    assert_eq!(config.targets[0].host_names, ["home.example.com"]);
+}
+
+#[test]
+fn fails_on_undefined_environment_variable() {
+    let e = load(
+        "undefined",
+        "host_name = \"home.example.com\"\n\n[duckdns]\ntoken = \"${DYNDNS_RS_TEST_UNDEFINED}\"\n",
+    )
+    .err()
+    .unwrap();
+
+    assert!(matches!(e, DNSUpdateError::Config(_)));
+    assert!(
+        e.to_string()
+            .contains("duckdns.token: environment variable DYNDNS_RS_TEST_UNDEFINED isn't set"),
+        "{e}"
+    );
+}
+
+/// Loads a config file expected to be invalid, returning the error message.
+fn load_error(name: &str, contents: &str) -> String {
+    let e = load(name, contents).err().unwrap();
+    assert!(matches!(e, DNSUpdateError::Config(_)), "{e}");
+    e.to_string()
+}
+
+#[test]
+fn rejects_several_hosts_for_record_id_provider() {
+    let e = load_error(
+        "record-id-hosts",
+        "host_name = \"home.example.com,other.example.com\"\nprovider = \"route53,hetzner\"\n",
+    );
+
+    assert!(e.contains("HETZNER_RECORD_ID"), "{e}");
+}
+
+#[test]
+fn accepts_one_host_for_record_id_provider() {
+    let config = load(
+        "record-id-host",
+        "host_name = \"home.example.com\"\nprovider = \"hetzner\"\n",
+    )
+    .unwrap();
+
+    assert_eq!(config.targets[0].host_names, ["home.example.com"]);
+}
+
+#[test]
+fn rejects_several_record_types_for_record_id_provider() {
+    let e = load_error(
+        "record-id-types",
+        "host_name = \"home.example.com\"\nprovider = \"vultr\"\nrecord_types = \"A,AAAA\"\n",
+    );
+
+    assert!(e.contains("VULTR_RECORD_ID"), "{e}");
+}
+
+#[test]
+fn accepts_record_id_provider_in_entry_of_one_record_type() {
+    let config = load(
+        "record-id-entry-type",
+        "record_types = \"A,AAAA\"\n\
+        \n\
+        [[records]]\n\
+        host_name = \"home.example.com\"\n\
+        provider = \"vultr\"\n\
+        record_type = \"AAAA\"\n",
+    )
+    .unwrap();
+
+    assert_eq!(config.targets.len(), 1);
+}
+
+#[test]
+fn rejects_audit_record_for_record_id_provider() {
+    let e = load_error(
+        "record-id-audit",
+        "host_name = \"home.example.com\"\nprovider = \"digitalocean\"\naudit_txt = true\n",
+    );
+
+    assert!(e.contains("DIGITALOCEAN_RECORD_ID"), "{e}");
+    assert!(e.contains("AUDIT_TXT"), "{e}");
+}
+
+/// Loads a config file of the host name for Route 53, returning the error
+/// message.
+fn host_name_error(name: &str, host_name: &str) -> String {
+    load_error(
+        name,
+        &format!("host_name = \"{host_name}\"\nprovider = \"route53\"\n"),
+    )
+}
+
+#[test]
+fn rejects_label_longer_than_63_characters() {
+    let label = "a".repeat(64);
+    let e = host_name_error("long-label", &format!("{label}.example.com"));
+
+    assert_eq!(
+        e,
+        format!("Host name {label}.example.com has a label longer than 63 characters")
+    );
+}
+
+#[test]
+fn rejects_label_starting_with_hyphen() {
+    let e = host_name_error("leading-hyphen", "-home.example.com");
+
+    assert_eq!(
+        e,
+        "Host name -home.example.com has a label starting or ending with a hyphen"
+    );
+}
+
+#[test]
+fn rejects_empty_label() {
+    let e = host_name_error("empty-label", "home..example.com");
+
+    assert_eq!(e, "Host name home..example.com has an empty label");
+}
+
+#[test]
+fn rejects_trailing_dot() {
+    let e = host_name_error("trailing-dot", "home.example.com.");
+
+    assert_eq!(e, "Host name home.example.com. has an empty label");
+}
+
+#[test]
+fn accepts_63_character_label_and_underscores() {
+    let host_name = format!("_acme-challenge.{}.example.com", "a".repeat(63));
+    let config = load(
+        "host-name-valid",
+        &format!("host_name = \"{host_name}\"\nprovider = \"route53\"\n"),
+    )
+    .unwrap();
+
+    assert_eq!(config.targets[0].host_names, [host_name]);
+}
+
+#[test]
+fn reads_secret_from_file_without_trailing_newline() {
+    let path = env::temp_dir().join(format!("dyndns-rs-test-secret-{}", std::process::id()));
+    std::fs::write(&path, "s3cret\n").unwrap();
+    let config = load(
+        "secret-file",
+        &format!(
+            "host_name = \"home.example.com\"\nprovider = \"duckdns\"\n\n[duckdns]\ntoken_file = \"{}\"\n",
+            path.display()
+        ),
+    )
+    .unwrap();
+
+    // Secrets are read when the updater is built, not when the config loads
+    let secret = config.targets[0]
+        .settings
+        .secret("DUCKDNS_TOKEN", "duckdns.token");
+    let _ = std::fs::remove_file(path);
+    assert_eq!(secret.unwrap(), "s3cret");
+}
+
+#[test]
+fn fails_on_missing_secret_file() {
+    let config = load(
+        "missing-secret-file",
+        "host_name = \"home.example.com\"\nprovider = \"duckdns\"\n\n[duckdns]\ntoken_file = \"/nonexistent/token\"\n",
+    )
+    .unwrap();
+
+    let e = config.targets[0]
+        .settings
+        .secret("DUCKDNS_TOKEN", "duckdns.token")
+        .unwrap_err();
+    assert!(matches!(e, DNSUpdateError::Config(_)), "{e}");
+    assert!(
+        e.to_string()
+            .contains("Unable to read secret file /nonexistent/token"),
+        "{e}"
+    );
+}
+
+#[test]
+fn leaves_secrets_out_of_debug_output() {
+    let config = load(
+        "debug",
+        "host_name = \"home.example.com\"\nprovider = \"duckdns\"\nip_source_headers = \"Authorization: Bearer s3cret-header\"\n\n[duckdns]\ntoken = \"s3cret-token\"\n",
+    )
+    .unwrap();
+
+    let debug = format!("{config:?}");
+    assert!(debug.contains("home.example.com"), "{debug}");
+    assert!(!debug.contains("s3cret"), "{debug}");
+}
+
+#[test]
+fn overrides_file_values_with_environment() {
+    // No other test of this file reads the TTL
+    env::set_var("TTL", "60");
+    let config = load(
+        "env-override",
+        "host_name = \"home.example.com\"\nprovider = \"duckdns\"\nttl = 300\nhttp_timeout = 5\n",
+    );
+    env::remove_var("TTL");
+    let config = config.unwrap();
+
+    assert_eq!(config.ttl, 60);
+    assert_eq!(config.http_timeout, Duration::from_secs(5));
+    assert_eq!(config.targets[0].host_names, ["home.example.com"]);
+    assert_eq!(config.targets[0].providers, [Provider::DuckDns]);
+}
+
+#[test]
+fn defaults_to_route53_when_provider_unset() {
+    // Route 53 was the only provider before PROVIDER existed
+    let config = load("provider-unset", "host_name = \"home.example.com\"\n").unwrap();
+
+    assert_eq!(config.targets[0].providers, [Provider::Route53]);
+}
+
+#[test]
+fn requires_provider_when_empty() {
+    let e = load_error(
+        "provider-empty",
+        "host_name = \"home.example.com\"\nprovider = \"\"\n",
+    );
+
+    assert_eq!(
+        e,
+        format!(
+            "PROVIDER is required; set it to one of {}",
+            Provider::names()
+        )
+    );
+}
+
+#[test]
+fn requires_provider_of_record_entry() {
+    let e = load_error(
+        "provider-entry-empty",
+        "[[records]]\nhost_name = \"home.example.com\"\nprovider = \"\"\n",
+    );
+
+    assert_eq!(e, "Invalid [[records]] entry 1: missing provider");
+}
@@ -0,0 +1,25 @@
+//! Spreads out the runs of the daemon.
+
+use dyndns_rs::jittered;
+use std::time::Duration;
+
+#[test]
+fn jitters_interval_within_bounds() {
+    let interval = Duration::from_secs(300);
+    let jitter = Duration::from_secs(30);
+    let mut rng = fastrand::Rng::with_seed(7);
+
+    for _ in 0..1000 {
+        let delay = jittered(interval, jitter, &mut rng);
+        assert!(delay >= interval, "{delay:?}");
+        assert!(delay <= interval + jitter, "{delay:?}");
+    }
+}
+
+#[test]
+fn leaves_interval_without_jitter() {
+    let interval = Duration::from_secs(300);
+    let mut rng = fastrand::Rng::with_seed(7);
+
+    assert_eq!(jittered(interval, Duration::ZERO, &mut rng), interval);
+}
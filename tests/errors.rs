@@ -0,0 +1,72 @@
+//! Maps each kind of failure to the exit code of the process.
+
+use dyndns_rs::providers::Provider;
+use dyndns_rs::sources::IpError;
+use dyndns_rs::DNSUpdateError;
+use std::io;
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// An error from reqwest, which can't be made directly.
+fn reqwest_error() -> reqwest::Error {
+    reqwest::Client::new().get("not a url").build().unwrap_err()
+}
+
+#[test]
+fn maps_errors_to_exit_codes() {
+    let route53_error = aws_sdk_route53::Error::InvalidInput(
+        aws_sdk_route53::types::error::InvalidInput::builder().build(),
+    );
+    let cases = [
+        (DNSUpdateError::Config(String::from("missing")), 2),
+        (
+            DNSUpdateError::Detect(IpError::Fixed(IpAddr::from([203, 0, 113, 7]))),
+            3,
+        ),
+        (
+            DNSUpdateError::Lookup(
+                String::from("home.example.com"),
+                io::Error::new(io::ErrorKind::NotFound, "not found"),
+            ),
+            3,
+        ),
+        (DNSUpdateError::Route53(Box::new(route53_error)), 4),
+        (DNSUpdateError::DigitalOcean(reqwest_error()), 4),
+        (DNSUpdateError::GoogleDns("rejected".into()), 4),
+        (DNSUpdateError::Gandi(reqwest_error()), 4),
+        (DNSUpdateError::Hetzner(reqwest_error()), 4),
+        (DNSUpdateError::DuckDns("KO".into()), 4),
+        (DNSUpdateError::Namecheap("rejected".into()), 4),
+        (DNSUpdateError::Rfc2136("refused".into()), 4),
+        (
+            DNSUpdateError::Http {
+                provider: Provider::Linode,
+                status: Some(401),
+                message: String::from("unauthorized"),
+            },
+            4,
+        ),
+        (
+            DNSUpdateError::AddrParse("garbage".parse::<IpAddr>().unwrap_err()),
+            4,
+        ),
+        (DNSUpdateError::Drift(1), 5),
+        (DNSUpdateError::SelfTest(1), 6),
+        (DNSUpdateError::Timeout(Duration::from_secs(1)), 7),
+    ];
+
+    for (e, exit_code) in cases {
+        assert_eq!(e.exit_code(), exit_code, "{e}");
+    }
+}
+
+#[test]
+fn names_provider_of_http_error() {
+    let e = DNSUpdateError::Http {
+        provider: Provider::Vultr,
+        status: Some(400),
+        message: String::from("Invalid record data"),
+    };
+
+    assert_eq!(e.to_string(), "Vultr error: Invalid record data");
+}
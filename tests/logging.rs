@@ -0,0 +1,104 @@
+//! Logs the requests to provider APIs at debug level without their secrets.
+
+use axum::http::Method;
+use axum::Router;
+use dyndns_rs::providers::{DNSUpdater, DnsRecord, DuckDnsUpdater, PorkbunUpdater, RecordType};
+use std::io;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tracing::Level;
+use tracing_subscriber::fmt::MakeWriter;
+
+const TOKEN: &str = "s3cret-token";
+const API_KEY: &str = "s3cret-key";
+const SECRET: &str = "s3cret-secret";
+
+/// A writer keeping everything logged to it.
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for Captured {
+    type Writer = Self;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+/// Serves DuckDNS's answer to GET requests and Porkbun's to POST requests,
+/// the latter repeating the key to check that responses are redacted too.
+async fn serve() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let router = Router::new().fallback(|method: Method| async move {
+        if method == Method::GET {
+            String::from("OK")
+        } else {
+            format!(r#"{{"status": "SUCCESS", "message": "Key {API_KEY} accepted"}}"#)
+        }
+    });
+    tokio::spawn(async move { axum::serve(listener, router).await });
+    format!("http://{address}")
+}
+
+/// Runs the update with debug logging, returning what was logged.
+async fn logged_update(updater: &dyn DNSUpdater, host_name: &str) -> String {
+    let captured = Captured::default();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(Level::DEBUG)
+        .with_ansi(false)
+        .with_writer(captured.clone())
+        .finish();
+    {
+        // The runtime of the test runs every task on this thread
+        let _guard = tracing::subscriber::set_default(subscriber);
+        updater
+            .update(DnsRecord::new(host_name, RecordType::A, "203.0.113.7", 300))
+            .await
+            .unwrap();
+    }
+    let logs = captured.0.lock().unwrap();
+    String::from_utf8_lossy(&logs).into_owned()
+}
+
+#[tokio::test]
+async fn redacts_token_in_query() {
+    let url = serve().await;
+    let updater = DuckDnsUpdater::new(reqwest::Client::new(), String::from(TOKEN))
+        .with_api_url(&format!("{url}/update"));
+    let logs = logged_update(&updater, "home.duckdns.org").await;
+
+    assert!(logs.contains("Sending request"), "{logs}");
+    assert!(logs.contains("token=%5Bredacted%5D"), "{logs}");
+    assert!(!logs.contains(TOKEN), "{logs}");
+}
+
+#[tokio::test]
+async fn redacts_keys_in_body() {
+    let url = serve().await;
+    let updater = PorkbunUpdater::new(
+        reqwest::Client::new(),
+        String::from(API_KEY),
+        String::from(SECRET),
+        String::from("example.com"),
+    )
+    .with_api_url(&url);
+    let logs = logged_update(&updater, "home.example.com").await;
+
+    assert!(logs.contains("Sending request"), "{logs}");
+    assert!(logs.contains("Received response"), "{logs}");
+    assert!(logs.contains("[redacted]"), "{logs}");
+    assert!(!logs.contains(API_KEY), "{logs}");
+    assert!(!logs.contains(SECRET), "{logs}");
+}
@@ -0,0 +1,110 @@
+//! Resolves host names to addresses of one family, through the system
+//! resolver and through a local server standing in for a DNS server, and
+//! detects the external IP address through that server.
+
+use dyndns_rs::resolver::{AddressFamily, Resolver, ServerResolver, SystemResolver};
+use dyndns_rs::sources::{DnsSource, IpSource};
+use hickory_client::op::{Message, MessageType};
+use hickory_client::rr::rdata::{A, AAAA};
+use hickory_client::rr::{RData, Record, RecordType};
+use hickory_client::serialize::binary::{BinDecodable, BinEncodable};
+use std::net::SocketAddr;
+use tokio::net::UdpSocket;
+
+const HOST_NAME: &str = "home.example.com";
+
+/// Serves an A and an AAAA record for every name on a free local port, except
+/// that names starting with v4only have no AAAA record.
+async fn serve() -> SocketAddr {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let address = socket.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut buffer = [0; 4096];
+        loop {
+            let (len, peer) = socket.recv_from(&mut buffer).await.unwrap();
+            let query = Message::from_bytes(&buffer[..len]).unwrap();
+            let mut response = Message::new();
+            response
+                .set_id(query.id())
+                .set_message_type(MessageType::Response)
+                .set_op_code(query.op_code())
+                .set_recursion_desired(query.recursion_desired())
+                .set_recursion_available(true);
+            for query in query.queries() {
+                response.add_query(query.clone());
+                let rdata = match query.query_type() {
+                    RecordType::A => RData::A(A::new(203, 0, 113, 7)),
+                    RecordType::AAAA if !query.name().to_string().starts_with("v4only.") => {
+                        RData::AAAA(AAAA::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 7))
+                    }
+                    _ => continue,
+                };
+                response.add_answer(Record::from_rdata(query.name().clone(), 300, rdata));
+            }
+            socket
+                .send_to(&response.to_bytes().unwrap(), peer)
+                .await
+                .unwrap();
+        }
+    });
+    address
+}
+
+#[tokio::test]
+async fn server_resolver_finds_address_in_family() {
+    let resolver = ServerResolver::new(serve().await);
+
+    let ip = resolver.lookup(HOST_NAME, AddressFamily::V4).await.unwrap();
+    assert_eq!(ip.as_deref(), Some("203.0.113.7"));
+    let ip = resolver.lookup(HOST_NAME, AddressFamily::V6).await.unwrap();
+    assert_eq!(ip.as_deref(), Some("2001:db8::7"));
+}
+
+#[tokio::test]
+async fn server_resolver_finds_no_aaaa_for_ipv4_only_host() {
+    let resolver = ServerResolver::new(serve().await);
+
+    let ip = resolver
+        .lookup("v4only.example.com", AddressFamily::V4)
+        .await
+        .unwrap();
+    assert_eq!(ip.as_deref(), Some("203.0.113.7"));
+    let ip = resolver
+        .lookup("v4only.example.com", AddressFamily::V6)
+        .await
+        .unwrap();
+    assert_eq!(ip, None);
+}
+
+#[tokio::test]
+async fn dns_source_detects_address_in_family() {
+    let server = serve().await;
+
+    let source = DnsSource::new("opendns", AddressFamily::V4)
+        .unwrap()
+        .with_server(server);
+    assert_eq!(source.detect().await.unwrap().to_string(), "203.0.113.7");
+    let source = DnsSource::new("opendns", AddressFamily::V6)
+        .unwrap()
+        .with_server(server);
+    assert_eq!(source.detect().await.unwrap().to_string(), "2001:db8::7");
+}
+
+#[tokio::test]
+async fn system_resolver_finds_address_in_family() {
+    let ip = SystemResolver
+        .lookup("localhost", AddressFamily::V4)
+        .await
+        .unwrap();
+
+    assert_eq!(ip.as_deref(), Some("127.0.0.1"));
+}
+
+#[tokio::test]
+async fn system_resolver_fails_for_unknown_host() {
+    let result = SystemResolver
+        .lookup("unknown.invalid", AddressFamily::Any)
+        .await;
+
+    assert!(result.is_err());
+}
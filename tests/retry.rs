@@ -0,0 +1,176 @@
+//! Limits retries with exponential backoff.
+
+use async_trait::async_trait;
+use dyndns_rs::providers::{DNSUpdater, DnsRecord, Provider, RecordType};
+use dyndns_rs::retry::Backoff;
+use dyndns_rs::sources::IpError;
+use dyndns_rs::{update_concurrently, DNSUpdateError};
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+/// Runs an operation that always fails retryably, returning how many times
+/// it was attempted.
+async fn attempts(backoff: &Backoff) -> u32 {
+    let attempts = AtomicU32::new(0);
+    let result: Result<(), _> = backoff
+        .retry(|| async {
+            attempts.fetch_add(1, Ordering::Relaxed);
+            Err(DNSUpdateError::Detect(IpError::Fixed(IpAddr::V4(
+                Ipv4Addr::LOCALHOST,
+            ))))
+        })
+        .await;
+    assert!(result.is_err());
+    attempts.into_inner()
+}
+
+#[test]
+fn caps_delays() {
+    let backoff = Backoff::new(10, Duration::from_millis(500), Duration::from_secs(3), None);
+
+    assert_eq!(backoff.delay(1), Duration::from_millis(500));
+    assert_eq!(backoff.delay(3), Duration::from_secs(2));
+    assert_eq!(backoff.delay(4), Duration::from_secs(3));
+    assert_eq!(backoff.delay(64), Duration::from_secs(3));
+}
+
+#[tokio::test]
+async fn retries_each_operation_without_budget() {
+    let backoff = Backoff::new(3, Duration::from_millis(1), Duration::from_millis(1), None);
+
+    assert_eq!(attempts(&backoff).await, 4);
+    assert_eq!(attempts(&backoff).await, 4);
+}
+
+#[tokio::test]
+async fn shares_budget_across_operations() {
+    let backoff = Backoff::new(
+        3,
+        Duration::from_millis(1),
+        Duration::from_millis(1),
+        Some(4),
+    );
+
+    // The first operation takes 3 retries, leaving 1 for the second
+    assert_eq!(attempts(&backoff).await, 4);
+    assert_eq!(attempts(&backoff).await, 2);
+    assert_eq!(attempts(&backoff).await, 1);
+
+    backoff.reset_budget();
+    assert_eq!(attempts(&backoff).await, 4);
+}
+
+/// An updater failing with the HTTP status until it's been called a number of
+/// times.
+struct FlakyUpdater {
+    status: u16,
+    failures: u32,
+    calls: AtomicU32,
+}
+
+impl FlakyUpdater {
+    fn new(status: u16, failures: u32) -> Self {
+        Self {
+            status,
+            failures,
+            calls: AtomicU32::new(0),
+        }
+    }
+}
+
+#[async_trait]
+impl DNSUpdater for FlakyUpdater {
+    async fn update(&self, _record: DnsRecord) -> Result<(), DNSUpdateError> {
+        if self.calls.fetch_add(1, Ordering::Relaxed) < self.failures {
+            return Err(DNSUpdateError::Http {
+                provider: Provider::Vultr,
+                status: Some(self.status),
+                message: String::from("failed"),
+            });
+        }
+        Ok(())
+    }
+
+    async fn current_record(
+        &self,
+        _host_name: &str,
+        _record_type: RecordType,
+    ) -> Result<Option<String>, DNSUpdateError> {
+        Ok(None)
+    }
+}
+
+async fn update(backoff: &Backoff, updater: &FlakyUpdater) -> Result<(), DNSUpdateError> {
+    let record = DnsRecord::new("home.example.com", RecordType::A, "203.0.113.7", 300);
+    backoff.retry(|| updater.update(record.clone())).await
+}
+
+#[tokio::test]
+async fn retries_updater_until_it_succeeds() {
+    let backoff = Backoff::new(3, Duration::from_millis(1), Duration::from_millis(1), None);
+    let updater = FlakyUpdater::new(503, 2);
+
+    update(&backoff, &updater).await.unwrap();
+    assert_eq!(updater.calls.into_inner(), 3);
+}
+
+#[tokio::test]
+async fn tries_updater_once_on_auth_failure() {
+    let backoff = Backoff::new(3, Duration::from_millis(1), Duration::from_millis(1), None);
+    let updater = FlakyUpdater::new(401, 2);
+
+    let e = update(&backoff, &updater).await.unwrap_err();
+    assert!(
+        matches!(
+            e,
+            DNSUpdateError::Http {
+                status: Some(401),
+                ..
+            }
+        ),
+        "{e}"
+    );
+    assert_eq!(updater.calls.into_inner(), 1);
+}
+
+#[tokio::test]
+async fn updates_other_providers_despite_failure() {
+    let backoff = Backoff::new(3, Duration::from_millis(1), Duration::from_millis(1), None);
+    let failing = FlakyUpdater::new(401, 1);
+    let working = FlakyUpdater::new(503, 0);
+    let record = DnsRecord::new("home.example.com", RecordType::A, "203.0.113.7", 300);
+
+    let results = update_concurrently(
+        &backoff,
+        vec![
+            (Provider::Vultr, &failing, vec![record.clone()]),
+            (Provider::Linode, &working, vec![record]),
+        ],
+    )
+    .await;
+    assert!(results[0][0].is_err());
+    assert!(results[1][0].is_ok());
+    assert_eq!(failing.calls.into_inner(), 1);
+    assert_eq!(working.calls.into_inner(), 1);
+}
+
+#[tokio::test]
+async fn retries_http_errors_of_server_and_throttling_only() {
+    let backoff = Backoff::new(3, Duration::from_millis(1), Duration::from_millis(1), None);
+    let cases = [
+        (500, true),
+        (503, true),
+        (429, true),
+        (400, false),
+        (403, false),
+        (404, false),
+    ];
+
+    for (status, retryable) in cases {
+        let updater = FlakyUpdater::new(status, 1);
+        let result = update(&backoff, &updater).await;
+        assert_eq!(result.is_ok(), retryable, "{status}");
+        assert_eq!(updater.calls.into_inner(), if retryable { 2 } else { 1 });
+    }
+}
@@ -0,0 +1,1625 @@
+//! Runs the program end to end against a local server standing in for both the
+//! IP detection service and the Route 53 API.
+
+use axum::extract::State;
+use axum::http::{HeaderMap, Method, StatusCode, Uri};
+use axum::Router;
+use clap::Parser;
+use dyndns_rs::providers::{
+    hosted_zone_id_by_name, reverse_name, route53_client, DNSUpdater, DnsRecord, Provider,
+    RecordType, Route53Updater,
+};
+use dyndns_rs::state::{save_state, PendingIp, PushedRecord, SetRecord, State as SavedState};
+use dyndns_rs::{run, Args, DNSUpdateError, USER_AGENT, VERSION};
+use std::env;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::{Arc, Mutex, Once};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::net::TcpListener;
+
+const HOST_NAME: &str = "home.example.com";
+const EXTERNAL_IP: &str = "203.0.113.7";
+const EXTERNAL_IPV6: &str = "2001:db8::7";
+const FAILING_ZONE: &str = "Z2";
+
+struct Mock {
+    /// The address the record currently has at Route 53
+    current_ip: &'static str,
+    /// The status of changes, to simulate failures of the API
+    change_status: StatusCode,
+    /// The method, path and body of every request to the Route 53 API
+    requests: Mutex<Vec<(Method, String, String)>>,
+    /// The headers of the last request to the IP detection service
+    ip_headers: Mutex<HeaderMap>,
+}
+
+async fn handle(
+    State(mock): State<Arc<Mock>>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: String,
+) -> (StatusCode, String) {
+    if uri.path() == "/fail" {
+        return (StatusCode::INTERNAL_SERVER_ERROR, String::new());
+    }
+    if uri.path() == "/slow" {
+        // Answers well after the timeout of the tests that use it
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        return (StatusCode::OK, format!("{EXTERNAL_IP}\n"));
+    }
+    if uri.path() == "/ip" {
+        // Answers with the address of the family the request was sent over
+        let external_ip = if headers["host"].to_str().unwrap().starts_with('[') {
+            EXTERNAL_IPV6
+        } else {
+            EXTERNAL_IP
+        };
+        *mock.ip_headers.lock().unwrap() = headers;
+        return (StatusCode::OK, format!("{external_ip}\n"));
+    }
+    mock.requests
+        .lock()
+        .unwrap()
+        .push((method.clone(), String::from(uri.path()), body));
+    // Changes to the failing zone are always rejected, so that a run can fail
+    // for some records only
+    let change_status = if uri.path().contains(&format!("/hostedzone/{FAILING_ZONE}/")) {
+        StatusCode::BAD_REQUEST
+    } else {
+        mock.change_status
+    };
+    if uri.path() == "/webhook" {
+        return (StatusCode::OK, String::new());
+    }
+    if uri.path() == "/2013-04-01/hostedzonesbyname" {
+        // Private zones can share the name of a public zone
+        return (
+            StatusCode::OK,
+            String::from(
+                "<?xml version=\"1.0\"?>\
+                <ListHostedZonesByNameResponse xmlns=\"https://route53.amazonaws.com/doc/2013-04-01/\">\
+                <HostedZones>\
+                <HostedZone><Id>/hostedzone/Z1</Id><Name>example.com.</Name>\
+                <CallerReference>1</CallerReference></HostedZone>\
+                <HostedZone><Id>/hostedzone/Z3</Id><Name>internal.example.com.</Name>\
+                <CallerReference>3</CallerReference></HostedZone>\
+                <HostedZone><Id>/hostedzone/Z4</Id><Name>internal.example.com.</Name>\
+                <CallerReference>4</CallerReference></HostedZone>\
+                </HostedZones>\
+                <IsTruncated>false</IsTruncated><MaxItems>100</MaxItems>\
+                </ListHostedZonesByNameResponse>",
+            ),
+        );
+    }
+    match method {
+        Method::GET => (
+            StatusCode::OK,
+            format!(
+                "<?xml version=\"1.0\"?>\
+                <ListResourceRecordSetsResponse xmlns=\"https://route53.amazonaws.com/doc/2013-04-01/\">\
+                <ResourceRecordSets><ResourceRecordSet>\
+                <Name>{HOST_NAME}.</Name><Type>{}</Type><TTL>300</TTL>\
+                <ResourceRecords><ResourceRecord><Value>{}</Value></ResourceRecord></ResourceRecords>\
+                </ResourceRecordSet></ResourceRecordSets>\
+                <IsTruncated>false</IsTruncated><MaxItems>1</MaxItems>\
+                </ListResourceRecordSetsResponse>",
+                if mock.current_ip.contains(':') { "AAAA" } else { "A" },
+                mock.current_ip
+            ),
+        ),
+        _ if change_status.is_success() => (
+            change_status,
+            String::from(
+                "<?xml version=\"1.0\"?>\
+                <ChangeResourceRecordSetsResponse xmlns=\"https://route53.amazonaws.com/doc/2013-04-01/\">\
+                <ChangeInfo><Id>/change/C1</Id><Status>PENDING</Status>\
+                <SubmittedAt>2024-01-01T00:00:00Z</SubmittedAt></ChangeInfo>\
+                </ChangeResourceRecordSetsResponse>",
+            ),
+        ),
+        _ => (
+            change_status,
+            String::from(
+                "<?xml version=\"1.0\"?>\
+                <ErrorResponse xmlns=\"https://route53.amazonaws.com/doc/2013-04-01/\">\
+                <Error><Type>Sender</Type><Code>InvalidChangeBatch</Code>\
+                <Message>Invalid change</Message></Error>\
+                <RequestId>1</RequestId></ErrorResponse>",
+            ),
+        ),
+    }
+}
+
+/// Serves the mock on a free local port of both address families, returning
+/// its IPv4 address.
+async fn serve(mock: &Arc<Mock>) -> SocketAddr {
+    let listener = TcpListener::bind("[::]:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let router = Router::new().fallback(handle).with_state(Arc::clone(mock));
+    tokio::spawn(async move { axum::serve(listener, router).await });
+    SocketAddr::from(([127, 0, 0, 1], port))
+}
+
+/// The SDK only takes credentials from the environment.
+fn set_credentials() {
+    static CREDENTIALS: Once = Once::new();
+    CREDENTIALS.call_once(|| {
+        env::set_var("AWS_ACCESS_KEY_ID", "AKIDEXAMPLE");
+        env::set_var("AWS_SECRET_ACCESS_KEY", "secret");
+    });
+}
+
+/// The arguments to run once against the mock, comparing with the record
+/// Route 53 has.
+fn args(address: SocketAddr, state_file: &Path, settings: &[&str]) -> Vec<String> {
+    let mut args = vec![
+        String::from("dyndns-rs"),
+        String::from("--provider=route53"),
+        // Given as a setting so that tests can override it
+        format!("--set=HOST_NAME={HOST_NAME}"),
+        // Given as a setting so that tests can override it
+        format!("--set=IP_SOURCES=http://{address}/ip"),
+        String::from("--compare-source=provider"),
+        String::from("--max-retries=0"),
+        format!("--state-file={}", state_file.display()),
+        String::from("--set=HOSTED_ZONE_ID=Z1"),
+        String::from("--set=AWS_ROUTE53_REGION=us-east-1"),
+        format!("--set=AWS_ROUTE53_ENDPOINT=http://{address}"),
+    ];
+    args.extend(settings.iter().map(|setting| format!("--set={setting}")));
+    args
+}
+
+fn state_file(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("dyndns-rs-test-{name}-{}.json", std::process::id()))
+}
+
+/// Runs once against the mock.
+async fn run_against(
+    mock: &Arc<Mock>,
+    name: &str,
+    settings: &[&str],
+) -> Result<(), DNSUpdateError> {
+    let address = serve(mock).await;
+    set_credentials();
+    let state_file = state_file(name);
+    let result = run(Args::parse_from(args(address, &state_file, settings))).await;
+    let _ = std::fs::remove_file(state_file);
+    result
+}
+
+/// Runs the binary with the arguments, to see what it prints.
+async fn output(args: Vec<String>) -> Output {
+    tokio::task::spawn_blocking(move || {
+        Command::new(env!("CARGO_BIN_EXE_dyndns-rs"))
+            .args(&args[1..])
+            .env("AWS_ACCESS_KEY_ID", "AKIDEXAMPLE")
+            .env("AWS_SECRET_ACCESS_KEY", "secret")
+            // Leaves out the SDK's own logs
+            .env("RUST_LOG", "dyndns_rs=info")
+            .output()
+            .unwrap()
+    })
+    .await
+    .unwrap()
+}
+
+/// Runs the binary once against the mock, to see what it prints.
+async fn run_binary(mock: &Arc<Mock>, name: &str, settings: &[&str]) -> Output {
+    let address = serve(mock).await;
+    let state_file = state_file(name);
+    let output = output(args(address, &state_file, settings)).await;
+    let _ = std::fs::remove_file(state_file);
+    output
+}
+
+/// Starts the binary in daemon mode against the mock, running every interval
+/// seconds.
+async fn spawn_daemon(mock: &Arc<Mock>, name: &str, interval: u64, settings: &[&str]) -> Child {
+    let address = serve(mock).await;
+    spawn(args(address, &state_file(name), settings), interval)
+}
+
+/// Starts the binary in daemon mode with the arguments.
+fn spawn(args: Vec<String>, interval: u64) -> Child {
+    Command::new(env!("CARGO_BIN_EXE_dyndns-rs"))
+        .args(&args[1..])
+        .arg(format!("--daemon-interval={interval}"))
+        .env("AWS_ACCESS_KEY_ID", "AKIDEXAMPLE")
+        .env("AWS_SECRET_ACCESS_KEY", "secret")
+        .env("RUST_LOG", "dyndns_rs=info")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap()
+}
+
+/// Waits until the mock has received the number of requests to the Route 53
+/// API, failing after a while.
+async fn wait_for_requests(mock: &Mock, count: usize) {
+    for _ in 0..100 {
+        if mock.requests.lock().unwrap().len() >= count {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("the daemon didn't send {count} requests");
+}
+
+/// Stops the daemon with SIGTERM, returning what it printed.
+async fn terminate(name: &str, daemon: Child) -> Output {
+    let status = Command::new("kill")
+        .arg("-TERM")
+        .arg(daemon.id().to_string())
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let output = tokio::task::spawn_blocking(move || daemon.wait_with_output().unwrap())
+        .await
+        .unwrap();
+    let _ = std::fs::remove_file(state_file(name));
+    output
+}
+
+fn mock(current_ip: &'static str, change_status: StatusCode) -> Arc<Mock> {
+    Arc::new(Mock {
+        current_ip,
+        change_status,
+        requests: Mutex::default(),
+        ip_headers: Mutex::default(),
+    })
+}
+
+fn changes(mock: &Mock) -> Vec<String> {
+    changes_in(mock, "Z1")
+}
+
+/// The change batches sent to the hosted zone.
+fn changes_in(mock: &Mock, hosted_zone_id: &str) -> Vec<String> {
+    let path = format!("/2013-04-01/hostedzone/{hosted_zone_id}/rrset");
+    mock.requests
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(method, request_path, _)| method == Method::POST && *request_path == path)
+        .map(|(_, _, body)| body.clone())
+        .collect()
+}
+
+/// The lookups of hosted zones by name.
+fn zone_lookups(mock: &Mock) -> usize {
+    mock.requests
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(_, path, _)| path == "/2013-04-01/hostedzonesbyname")
+        .count()
+}
+
+/// The payloads posted to the webhook.
+fn webhooks(mock: &Mock) -> Vec<serde_json::Value> {
+    mock.requests
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(method, path, _)| method == Method::POST && path == "/webhook")
+        .map(|(_, _, body)| serde_json::from_str(body).unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn updates_changed_record() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    run_against(&mock, "changed", &[]).await.unwrap();
+
+    let changes = changes(&mock);
+    assert_eq!(changes.len(), 1);
+    assert!(changes[0].contains("<Action>UPSERT</Action>"));
+    assert!(changes[0].contains(&format!("<Name>{HOST_NAME}</Name>")));
+    assert!(changes[0].contains("<Type>A</Type>"));
+    assert!(changes[0].contains(&format!("<Value>{EXTERNAL_IP}</Value>")));
+}
+
+#[tokio::test]
+async fn updates_aaaa_record() {
+    let mock = mock("2001:db8::2", StatusCode::OK);
+    run_against(&mock, "aaaa", &["EXTERNAL_IP=2001:db8::1"])
+        .await
+        .unwrap();
+
+    let changes = changes(&mock);
+    assert_eq!(changes.len(), 1);
+    assert!(changes[0].contains(&format!("<Name>{HOST_NAME}</Name>")));
+    assert!(changes[0].contains("<Type>AAAA</Type>"));
+    assert!(changes[0].contains("<Value>2001:db8::1</Value>"));
+}
+
+#[tokio::test]
+async fn sets_configured_ttl() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    run_against(&mock, "ttl", &["TTL=600"]).await.unwrap();
+
+    let changes = changes(&mock);
+    assert_eq!(changes.len(), 1);
+    assert!(changes[0].contains("<TTL>600</TTL>"));
+}
+
+#[tokio::test]
+async fn fails_on_invalid_ttl() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    let e = run_against(&mock, "invalid-ttl", &["TTL=soon"])
+        .await
+        .unwrap_err();
+
+    assert!(matches!(e, DNSUpdateError::Config(_)), "{e}");
+    assert!(e.to_string().contains("TTL"), "{e}");
+    assert!(mock.requests.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn leaves_unchanged_record() {
+    let mock = mock(EXTERNAL_IP, StatusCode::OK);
+    run_against(&mock, "unchanged", &[]).await.unwrap();
+
+    assert!(changes(&mock).is_empty());
+    // The record was still read
+    assert_eq!(mock.requests.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn batches_hosts_of_same_zone() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    let host_names = format!("HOST_NAME={HOST_NAME},other.example.com");
+    run_against(&mock, "batched", &[&host_names]).await.unwrap();
+
+    let changes = changes(&mock);
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].matches("<Change>").count(), 2);
+    assert!(changes[0].contains(&format!("<Name>{HOST_NAME}</Name>")));
+    assert!(changes[0].contains("<Name>other.example.com</Name>"));
+}
+
+#[tokio::test]
+async fn updates_only_changed_host() {
+    // The mock only has the record of HOST_NAME, so the other one is missing
+    let mock = mock(EXTERNAL_IP, StatusCode::OK);
+    let host_names = format!("HOST_NAME={HOST_NAME},other.example.com");
+    run_against(&mock, "one-changed", &[&host_names])
+        .await
+        .unwrap();
+
+    let changes = changes(&mock);
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].matches("<Change>").count(), 1);
+    assert!(changes[0].contains("<Name>other.example.com</Name>"));
+}
+
+#[tokio::test]
+async fn fails_every_host_of_rejected_batch() {
+    let mock = mock("198.51.100.1", StatusCode::BAD_REQUEST);
+    let host_names = format!("HOST_NAME={HOST_NAME},other.example.com");
+    let e = run_against(&mock, "batch-rejected", &[&host_names])
+        .await
+        .unwrap_err();
+
+    assert!(matches!(e, DNSUpdateError::Route53(_)), "{e}");
+    assert_eq!(changes(&mock).len(), 1);
+}
+
+#[tokio::test]
+async fn gives_batch_error_to_each_host() {
+    let mock = mock("198.51.100.1", StatusCode::BAD_REQUEST);
+    let address = serve(&mock).await;
+    let state_file = state_file("batch-errors");
+    let args = args(
+        address,
+        &state_file,
+        &[
+            &format!("HOST_NAME={HOST_NAME},other.example.com"),
+            "OUTPUT=json",
+        ],
+    );
+    let output = output(args).await;
+    let _ = std::fs::remove_file(state_file);
+
+    assert_eq!(output.status.code(), Some(4));
+    assert_eq!(changes(&mock).len(), 1);
+    let summary: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let hosts = summary["hosts"].as_array().unwrap();
+    assert_eq!(hosts.len(), 2);
+    for host in hosts {
+        assert_eq!(host["status"], "failed");
+        let error = host["error"].as_str().unwrap();
+        assert!(error.starts_with("Route 53 error: "), "{error}");
+        assert!(error.contains("InvalidChangeBatch"), "{error}");
+    }
+}
+
+#[tokio::test]
+async fn forces_update_of_unchanged_record() {
+    let mock = mock(EXTERNAL_IP, StatusCode::OK);
+    let output = run_binary(&mock, "forced", &["FORCE_UPDATE=true"]).await;
+
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Forcing update of A record"), "{stderr}");
+    let changes = changes(&mock);
+    assert_eq!(changes.len(), 1);
+    assert!(changes[0].contains(&format!("<Value>{EXTERNAL_IP}</Value>")));
+}
+
+#[tokio::test]
+async fn leaves_record_with_other_spelling_of_address() {
+    let mock = mock("2001:0db8:0000:0000:0000:0000:0000:0001", StatusCode::OK);
+    run_against(&mock, "ipv6-spelling", &["EXTERNAL_IP=2001:db8::1"])
+        .await
+        .unwrap();
+
+    assert!(changes(&mock).is_empty());
+    assert_eq!(mock.requests.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn updates_a_record_only() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    run_against(&mock, "a-only", &["RECORD_TYPES=A"])
+        .await
+        .unwrap();
+
+    let changes = changes(&mock);
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].matches("<Change>").count(), 1);
+    assert!(changes[0].contains(&format!("<Value>{EXTERNAL_IP}</Value>")));
+}
+
+#[tokio::test]
+async fn updates_aaaa_record_only() {
+    let mock = mock("2001:db8::1", StatusCode::OK);
+    run_against(
+        &mock,
+        "aaaa-only",
+        &["RECORD_TYPES=AAAA", "EXTERNAL_IP=2001:db8::7"],
+    )
+    .await
+    .unwrap();
+
+    let changes = changes(&mock);
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].matches("<Change>").count(), 1);
+    assert!(changes[0].contains("<Type>AAAA</Type>"));
+    assert!(changes[0].contains(&format!("<Value>{EXTERNAL_IPV6}</Value>")));
+}
+
+#[tokio::test]
+async fn updates_both_records_of_dual_stack_host() {
+    // Each family only reaches the mock over its own loopback address
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    let address = serve(&mock).await;
+    let ip_sources = format!(
+        "IP_SOURCES=http://{address}/ip,http://[::1]:{}/ip",
+        address.port()
+    );
+    run_against(&mock, "dual-stack", &["RECORD_TYPES=A,AAAA", &ip_sources])
+        .await
+        .unwrap();
+
+    let changes = changes(&mock);
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].matches("<Change>").count(), 2);
+    assert!(changes[0].contains(&format!("<Value>{EXTERNAL_IP}</Value>")));
+    assert!(changes[0].contains("<Type>AAAA</Type>"));
+    assert!(changes[0].contains(&format!("<Value>{EXTERNAL_IPV6}</Value>")));
+}
+
+#[tokio::test]
+async fn updates_audit_record_after_address_record() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    run_against(&mock, "audit", &["AUDIT_TXT=true"])
+        .await
+        .unwrap();
+
+    let changes = changes(&mock);
+    assert_eq!(changes.len(), 2);
+    assert!(changes[0].contains("<Type>A</Type>"));
+    assert!(changes[1].contains(&format!("<Name>_dyndns.{HOST_NAME}</Name>")));
+    assert!(changes[1].contains("<Type>TXT</Type>"));
+    assert!(changes[1].contains("ip_source="));
+}
+
+#[tokio::test]
+async fn updates_no_audit_record_after_failed_update() {
+    let mock = mock("198.51.100.1", StatusCode::BAD_REQUEST);
+    run_against(&mock, "audit-failed", &["AUDIT_TXT=true"])
+        .await
+        .unwrap_err();
+
+    let changes = changes(&mock);
+    assert_eq!(changes.len(), 1);
+    assert!(!changes[0].contains("_dyndns"));
+}
+
+#[tokio::test]
+async fn updates_weighted_record() {
+    // The record the mock has is a simple one, so the weighted one is missing
+    let mock = mock(EXTERNAL_IP, StatusCode::OK);
+    run_against(
+        &mock,
+        "weighted",
+        &["ROUTE53_SET_IDENTIFIER=home", "ROUTE53_WEIGHT=10"],
+    )
+    .await
+    .unwrap();
+
+    let changes = changes(&mock);
+    assert_eq!(changes.len(), 1);
+    assert!(changes[0].contains("<SetIdentifier>home</SetIdentifier>"));
+    assert!(changes[0].contains("<Weight>10</Weight>"));
+}
+
+#[tokio::test]
+async fn updates_cname_record() {
+    // The mock only has an A record, so the CNAME record is missing
+    let mock = mock(EXTERNAL_IP, StatusCode::OK);
+    run_against(
+        &mock,
+        "cname",
+        &["RECORD_TYPE=CNAME", "RECORD_VALUE=target.example.net"],
+    )
+    .await
+    .unwrap();
+
+    let changes = changes(&mock);
+    assert_eq!(changes.len(), 1);
+    assert!(changes[0].contains("<Type>CNAME</Type>"));
+    assert!(changes[0].contains("<Value>target.example.net.</Value>"));
+    // The target is configured, so no address was detected
+    assert!(mock.ip_headers.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn falls_back_to_next_ip_source() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    let address = serve(&mock).await;
+    let ip_sources = format!("IP_SOURCES=http://{address}/fail,http://{address}/ip");
+    run_against(&mock, "fallback", &[&ip_sources])
+        .await
+        .unwrap();
+
+    let changes = changes(&mock);
+    assert_eq!(changes.len(), 1);
+    assert!(changes[0].contains(&format!("<Value>{EXTERNAL_IP}</Value>")));
+}
+
+#[tokio::test]
+async fn fails_when_every_ip_source_fails() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    let address = serve(&mock).await;
+    let ip_sources = format!("IP_SOURCES=http://{address}/fail,http://{address}/fail?again");
+    let e = run_against(&mock, "all-failed", &[&ip_sources])
+        .await
+        .unwrap_err();
+
+    assert!(matches!(e, DNSUpdateError::Detect(_)), "{e}");
+    assert_eq!(e.exit_code(), 3);
+    // Every source is named with its error
+    let message = e.to_string();
+    assert!(message.contains(&format!("http://{address}/fail: ")), "{e}");
+    assert!(message.contains("/fail?again: "), "{e}");
+    assert!(changes(&mock).is_empty());
+}
+
+#[tokio::test]
+async fn times_out_slow_ip_source() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    let address = serve(&mock).await;
+    let ip_sources = format!("IP_SOURCES=http://{address}/slow");
+    let started = std::time::Instant::now();
+    let e = run_against(&mock, "slow-source", &[&ip_sources, "HTTP_TIMEOUT=1"])
+        .await
+        .unwrap_err();
+
+    assert!(matches!(e, DNSUpdateError::Detect(_)), "{e}");
+    assert!(e.to_string().contains("timed out"), "{e}");
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert!(changes(&mock).is_empty());
+}
+
+#[tokio::test]
+async fn gives_up_on_run_after_run_timeout() {
+    // The source would answer within its own timeout, but not the run's
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    let address = serve(&mock).await;
+    let ip_sources = format!("IP_SOURCES=http://{address}/slow");
+    let started = std::time::Instant::now();
+    let output = run_binary(
+        &mock,
+        "run-timeout",
+        &[&ip_sources, "HTTP_TIMEOUT=30", "RUN_TIMEOUT=1"],
+    )
+    .await;
+
+    assert_eq!(output.status.code(), Some(7));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Run timed out after 1s"), "{stderr}");
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert!(changes(&mock).is_empty());
+}
+
+#[tokio::test]
+async fn quotes_txt_record() {
+    let mock = mock(EXTERNAL_IP, StatusCode::OK);
+    run_against(
+        &mock,
+        "txt",
+        &["RECORD_TYPE=TXT", r#"RECORD_VALUE=say "hi""#],
+    )
+    .await
+    .unwrap();
+
+    let changes = changes(&mock);
+    assert_eq!(changes.len(), 1);
+    assert!(changes[0].contains("<Type>TXT</Type>"));
+    // "say \"hi\"", escaped for XML
+    assert!(changes[0].contains(r"<Value>&quot;say \&quot;hi\&quot;&quot;</Value>"));
+}
+
+#[tokio::test]
+async fn sends_ip_source_headers() {
+    let mock = mock(EXTERNAL_IP, StatusCode::OK);
+    run_against(
+        &mock,
+        "headers",
+        &["IP_SOURCE_HEADERS=Authorization: Bearer x; Accept: text/x-ip"],
+    )
+    .await
+    .unwrap();
+
+    let headers = mock.ip_headers.lock().unwrap();
+    assert_eq!(headers["authorization"], "Bearer x");
+    assert_eq!(headers["accept"], "text/x-ip");
+}
+
+#[tokio::test]
+async fn detects_ip_through_proxy() {
+    // The IP source can't be resolved, so only the proxy can reach it
+    let mock = mock(EXTERNAL_IP, StatusCode::OK);
+    let address = serve(&mock).await;
+    set_credentials();
+    let state_file = state_file("proxy");
+    let proxy_url = format!("PROXY_URL=http://user:secret@{address}");
+    let result = run(Args::parse_from(args(
+        address,
+        &state_file,
+        &["IP_SOURCES=http://ip.example.invalid/ip", &proxy_url],
+    )))
+    .await;
+    let _ = std::fs::remove_file(state_file);
+
+    result.unwrap();
+    let headers = mock.ip_headers.lock().unwrap();
+    assert_eq!(headers["host"], "ip.example.invalid");
+    assert_eq!(headers["proxy-authorization"], "Basic dXNlcjpzZWNyZXQ=");
+}
+
+#[tokio::test]
+async fn sends_version_in_user_agent() {
+    let mock = mock(EXTERNAL_IP, StatusCode::OK);
+    run_against(&mock, "user-agent", &[]).await.unwrap();
+
+    assert!(!VERSION.is_empty());
+    assert_eq!(USER_AGENT, format!("dyndns-rs/{VERSION}"));
+    assert_eq!(mock.ip_headers.lock().unwrap()["user-agent"], USER_AGENT);
+}
+
+#[test]
+fn prints_version() {
+    let output = Command::new(env!("CARGO_BIN_EXE_dyndns-rs"))
+        .arg("--version")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("dyndns-rs {VERSION}\n")
+    );
+}
+
+#[tokio::test]
+async fn fails_when_change_is_rejected() {
+    let mock = mock("198.51.100.1", StatusCode::BAD_REQUEST);
+    let e = run_against(&mock, "rejected", &[]).await.unwrap_err();
+
+    assert!(matches!(e, DNSUpdateError::Route53(_)), "{e}");
+    assert_eq!(e.exit_code(), 4);
+    assert_eq!(changes(&mock).len(), 1);
+}
+
+#[tokio::test]
+async fn builds_updaters_of_each_record_entry() {
+    let mock = mock(EXTERNAL_IP, StatusCode::OK);
+    let address = serve(&mock).await;
+    set_credentials();
+    let config_file = env::temp_dir().join(format!(
+        "dyndns-rs-test-records-{}.toml",
+        std::process::id()
+    ));
+    std::fs::write(
+        &config_file,
+        format!(
+            "ip_sources = [\"http://{address}/ip\"]\n\
+            \n\
+            [[records]]\n\
+            provider = \"route53\"\n\
+            host_name = \"{HOST_NAME}\"\n\
+            route53 = {{ hosted_zone_id = \"Z2\", region = \"us-east-1\", endpoint_url = \"http://{address}\" }}\n\
+            \n\
+            [[records]]\n\
+            provider = \"duckdns\"\n\
+            host_name = \"home.duckdns.org\"\n\
+            duckdns = {{ token = \"token\" }}\n"
+        ),
+    )
+    .unwrap();
+    let result = run(Args::parse_from([
+        String::from("dyndns-rs"),
+        String::from("--selftest"),
+        format!("--config={}", config_file.display()),
+    ]))
+    .await;
+    let _ = std::fs::remove_file(config_file);
+
+    // Building the DuckDNS updater fails without the token of its entry
+    result.unwrap();
+    // The Route 53 record was read from the hosted zone of its entry
+    let requests = mock.requests.lock().unwrap();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].0, Method::GET);
+    assert_eq!(requests[0].1, "/2013-04-01/hostedzone/Z2/rrset");
+}
+
+#[tokio::test]
+async fn prints_nothing_for_unchanged_run_when_quiet() {
+    let output = run_binary(&mock(EXTERNAL_IP, StatusCode::OK), "quiet", &[]).await;
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("unchanged"));
+
+    let output = run_binary(&mock(EXTERNAL_IP, StatusCode::OK), "quiet", &["QUIET=true"]).await;
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(
+        output.stderr.is_empty(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[tokio::test]
+async fn prints_changes_when_quiet() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    let output = run_binary(&mock, "quiet-changed", &["QUIET=true"]).await;
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("updated"));
+}
+
+#[test]
+fn reverses_ipv4_address() {
+    assert_eq!(
+        reverse_name("203.0.113.7".parse().unwrap()),
+        "7.113.0.203.in-addr.arpa"
+    );
+}
+
+#[test]
+fn reverses_ipv6_address_by_nibble() {
+    assert_eq!(
+        reverse_name("2001:db8::1".parse().unwrap()),
+        "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa"
+    );
+}
+
+#[tokio::test]
+async fn updates_ptr_record_of_address() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    run_against(&mock, "ptr", &["UPDATE_PTR=true", "REVERSE_ZONE_ID=R1"])
+        .await
+        .unwrap();
+
+    assert_eq!(changes(&mock).len(), 1);
+    let reverse_changes = changes_in(&mock, "R1");
+    assert_eq!(reverse_changes.len(), 1);
+    assert!(reverse_changes[0].contains("<Name>7.113.0.203.in-addr.arpa</Name>"));
+    assert!(reverse_changes[0].contains("<Type>PTR</Type>"));
+    assert!(reverse_changes[0].contains(&format!("<Value>{HOST_NAME}.</Value>")));
+}
+
+#[tokio::test]
+async fn updates_no_ptr_record_of_txt_record() {
+    // The text looks like an address, but only address records have a reverse
+    let mock = mock(EXTERNAL_IP, StatusCode::OK);
+    run_against(
+        &mock,
+        "ptr-txt",
+        &[
+            "UPDATE_PTR=true",
+            "REVERSE_ZONE_ID=R1",
+            "RECORD_TYPE=TXT",
+            "RECORD_VALUE=198.51.100.1",
+        ],
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(changes(&mock).len(), 1);
+    assert!(changes_in(&mock, "R1").is_empty());
+}
+
+#[tokio::test]
+async fn reports_would_update_in_dry_run() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    let output = run_binary(&mock, "dry-run", &["DRY_RUN=true"]).await;
+
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("would update"));
+    assert!(changes(&mock).is_empty());
+}
+
+#[tokio::test]
+async fn reports_would_update_in_dry_run_json() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    let output = run_binary(&mock, "dry-run-json", &["DRY_RUN=true", "OUTPUT=json"]).await;
+
+    assert!(output.status.success());
+    let summary: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(summary["hosts"][0]["status"], "would_update");
+    assert!(changes(&mock).is_empty());
+}
+
+#[tokio::test]
+async fn checks_record_in_sync() {
+    let mock = mock(EXTERNAL_IP, StatusCode::OK);
+    let output = run_binary(&mock, "check-in-sync", &["CHECK=true"]).await;
+
+    assert_eq!(output.status.code(), Some(0));
+    assert!(changes(&mock).is_empty());
+}
+
+#[tokio::test]
+async fn checks_drifted_record() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    let output = run_binary(&mock, "check-drifted", &["CHECK=true"]).await;
+
+    assert_eq!(output.status.code(), Some(5));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(&format!("is 198.51.100.1, expected {EXTERNAL_IP}")),
+        "{stderr}"
+    );
+    assert!(changes(&mock).is_empty());
+}
+
+/// Runs the binary's self-test against the mock.
+async fn self_test(mock: &Arc<Mock>, name: &str, settings: &[&str]) -> Output {
+    let address = serve(mock).await;
+    let state_file = state_file(name);
+    let mut args = args(address, &state_file, settings);
+    args.push(String::from("--selftest"));
+    let output = output(args).await;
+    let _ = std::fs::remove_file(state_file);
+    output
+}
+
+#[tokio::test]
+async fn self_tests_without_changing_record() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    let output = self_test(&mock, "selftest", &[]).await;
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("[ok]   Route 53 credentials"), "{stdout}");
+    assert!(changes(&mock).is_empty());
+}
+
+#[tokio::test]
+async fn self_test_fails_on_failed_check() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    let address = serve(&mock).await;
+    let ip_sources = format!("IP_SOURCES=http://{address}/fail");
+    let output = self_test(&mock, "selftest-failed", &[&ip_sources]).await;
+
+    assert_eq!(output.status.code(), Some(6));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("[FAIL] IP detection from"), "{stdout}");
+    assert!(stdout.contains("[ok]   Route 53 credentials"), "{stdout}");
+}
+
+#[tokio::test]
+async fn summarizes_changed_record_in_json() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    let output = run_binary(&mock, "json-changed", &["OUTPUT=json"]).await;
+
+    assert!(output.status.success());
+    let summary: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(summary["external_ips"], serde_json::json!([EXTERNAL_IP]));
+    assert_eq!(summary["error"], serde_json::Value::Null);
+    let record = &summary["records"][0];
+    assert_eq!(record["provider"], "Route 53");
+    assert_eq!(record["host_name"], HOST_NAME);
+    assert_eq!(record["previous_ip"], "198.51.100.1");
+    assert_eq!(record["changed"], true);
+    assert_eq!(record["error"], serde_json::Value::Null);
+    assert_eq!(summary["hosts"][0]["status"], "updated");
+}
+
+#[tokio::test]
+async fn summarizes_unchanged_record_in_json() {
+    let mock = mock(EXTERNAL_IP, StatusCode::OK);
+    let output = run_binary(&mock, "json-unchanged", &["OUTPUT=json"]).await;
+
+    assert!(output.status.success());
+    let summary: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let record = &summary["records"][0];
+    assert_eq!(record["previous_ip"], EXTERNAL_IP);
+    assert_eq!(record["changed"], false);
+    assert_eq!(summary["hosts"][0]["status"], "unchanged");
+}
+
+#[tokio::test]
+async fn daemon_rate_limits_repeated_updates() {
+    let mock = mock(EXTERNAL_IP, StatusCode::OK);
+    // Forcing the update makes every run try to change the record
+    let daemon = spawn_daemon(&mock, "daemon-rate-limited", 1, &["FORCE_UPDATE=true"]).await;
+    // Forced updates skip reading the record, so the first run only changes
+    // it, and the next runs are left to the rate limiter
+    wait_for_requests(&mock, 1).await;
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    let output = terminate("daemon-rate-limited", daemon).await;
+
+    assert!(output.status.success());
+    assert_eq!(changes(&mock).len(), 1);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("updated less than 60s ago"), "{stderr}");
+}
+
+#[tokio::test]
+async fn daemon_builds_updaters_once() {
+    // Looking up the zone by name is part of building the Route 53 updater
+    let mock = mock(EXTERNAL_IP, StatusCode::OK);
+    let address = serve(&mock).await;
+    let mut args = args(
+        address,
+        &state_file("daemon-build-once"),
+        &["ROUTE53_DOMAIN=example.com", "FORCE_UPDATE=true"],
+    );
+    args.retain(|arg| arg != "--set=HOSTED_ZONE_ID=Z1");
+    let daemon = spawn(args, 1);
+    // Every run gets the updaters before the rate limiter skips the update
+    wait_for_requests(&mock, 2).await;
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    let output = terminate("daemon-build-once", daemon).await;
+
+    assert!(output.status.success());
+    assert_eq!(changes(&mock).len(), 1);
+    assert_eq!(zone_lookups(&mock), 1);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("updated less than 60s ago"), "{stderr}");
+}
+
+#[tokio::test]
+async fn daemon_shuts_down_between_runs() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    // Far enough apart that the signal comes before the second run
+    let daemon = spawn_daemon(&mock, "daemon-shutdown", 3600, &[]).await;
+    // A read and a change on the first run
+    wait_for_requests(&mock, 2).await;
+    let signalled = std::time::Instant::now();
+    let output = terminate("daemon-shutdown", daemon).await;
+
+    assert!(output.status.success());
+    assert!(signalled.elapsed() < Duration::from_secs(5));
+    assert_eq!(changes(&mock).len(), 1);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stderr.matches("Updating A record").count(), 1, "{stderr}");
+    let (_, after_signal) = stderr.split_once("Received SIGTERM").unwrap();
+    assert!(
+        after_signal.trim_end().ends_with("Shut down cleanly"),
+        "{stderr}"
+    );
+}
+
+#[tokio::test]
+async fn daemon_keeps_running_after_failed_run() {
+    let mock = mock("198.51.100.1", StatusCode::BAD_REQUEST);
+    // Failed updates count against the rate limit too
+    let daemon = spawn_daemon(&mock, "daemon-failed", 1, &["MIN_UPDATE_INTERVAL=0"]).await;
+    // A read and a rejected change on each run
+    wait_for_requests(&mock, 4).await;
+    let output = terminate("daemon-failed", daemon).await;
+
+    assert!(output.status.success());
+    assert!(changes(&mock).len() >= 2);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("InvalidChangeBatch"), "{stderr}");
+}
+
+/// An updater of the hosted zone Z1 at the mock.
+async fn updater(mock: &Arc<Mock>) -> Route53Updater {
+    Route53Updater::new(client(mock).await, String::from("Z1"), None, None)
+}
+
+/// Creates a client of the mock's Route 53 API.
+async fn client(mock: &Arc<Mock>) -> aws_sdk_route53::Client {
+    let address = serve(mock).await;
+    set_credentials();
+    route53_client(
+        None,
+        None,
+        Some(String::from("us-east-1")),
+        Some(&format!("http://{address}")),
+    )
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn finds_hosted_zone_by_name() {
+    let mock = mock(EXTERNAL_IP, StatusCode::OK);
+    let client = client(&mock).await;
+
+    assert_eq!(
+        hosted_zone_id_by_name(&client, "example.com.")
+            .await
+            .unwrap(),
+        "Z1"
+    );
+    // Zones from the domain on are listed, not only those with its name
+    let e = hosted_zone_id_by_name(&client, "example.net")
+        .await
+        .unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "No Route 53 hosted zone is named example.net"
+    );
+}
+
+#[tokio::test]
+async fn fails_on_several_hosted_zones_with_name() {
+    let mock = mock(EXTERNAL_IP, StatusCode::OK);
+    let e = hosted_zone_id_by_name(&client(&mock).await, "internal.example.com")
+        .await
+        .unwrap_err();
+
+    assert!(matches!(e, DNSUpdateError::Config(_)), "{e}");
+    assert!(e.to_string().contains("(Z3, Z4)"), "{e}");
+}
+
+#[tokio::test]
+async fn prefers_hosted_zone_id_to_lookup() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    let output = run_binary(&mock, "zone-by-id", &["ROUTE53_DOMAIN=example.com"]).await;
+
+    assert!(output.status.success());
+    assert_eq!(changes(&mock).len(), 1);
+    assert_eq!(zone_lookups(&mock), 0);
+}
+
+#[tokio::test]
+async fn reads_current_record() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    let current = updater(&mock)
+        .await
+        .current_record(HOST_NAME, RecordType::A)
+        .await
+        .unwrap();
+
+    assert_eq!(current.as_deref(), Some("198.51.100.1"));
+}
+
+#[tokio::test]
+async fn reads_missing_record() {
+    // Route 53 lists the records from the name on, so the mock's record is
+    // the next one
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    let updater = updater(&mock).await;
+
+    let current = updater
+        .current_record("other.example.com", RecordType::A)
+        .await
+        .unwrap();
+    assert_eq!(current, None);
+    let current = updater
+        .current_record(HOST_NAME, RecordType::Aaaa)
+        .await
+        .unwrap();
+    assert_eq!(current, None);
+}
+
+#[tokio::test]
+async fn translates_record_to_change() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    updater(&mock)
+        .await
+        .update(DnsRecord::new(
+            "text.example.com",
+            RecordType::Txt,
+            "hello",
+            120,
+        ))
+        .await
+        .unwrap();
+
+    let changes = changes(&mock);
+    assert_eq!(changes.len(), 1);
+    assert!(changes[0].contains("<Action>UPSERT</Action>"));
+    assert!(changes[0].contains("<Name>text.example.com</Name>"));
+    assert!(changes[0].contains("<Type>TXT</Type>"));
+    assert!(changes[0].contains("<TTL>120</TTL>"));
+    assert!(changes[0].contains("<Value>&quot;hello&quot;</Value>"));
+}
+
+#[tokio::test]
+async fn uses_default_credentials_without_role() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    let address = serve(&mock).await;
+    set_credentials();
+    let client = route53_client(
+        None,
+        None,
+        Some(String::from("us-east-1")),
+        Some(&format!("http://{address}")),
+    )
+    .await
+    .unwrap();
+
+    let current = Route53Updater::new(client, String::from("Z1"), None, None)
+        .current_record(HOST_NAME, RecordType::A)
+        .await
+        .unwrap();
+    assert_eq!(current.as_deref(), Some("198.51.100.1"));
+}
+
+#[tokio::test]
+async fn assumes_role_before_calling_route53() {
+    // The role can't be assumed with the test's credentials, so Route 53 is
+    // never called
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    let address = serve(&mock).await;
+    set_credentials();
+    let client = route53_client(
+        None,
+        Some("arn:aws:iam::123456789012:role/dyndns"),
+        Some(String::from("us-east-1")),
+        Some(&format!("http://{address}")),
+    )
+    .await
+    .unwrap();
+
+    let result = Route53Updater::new(client, String::from("Z1"), None, None)
+        .current_record(HOST_NAME, RecordType::A)
+        .await;
+    assert!(result.is_err());
+    assert!(mock.requests.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn notifies_webhook_of_change() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    let address = serve(&mock).await;
+    let webhook_url = format!("WEBHOOK_URL=http://{address}/webhook");
+    run_against(&mock, "webhook-changed", &[&webhook_url])
+        .await
+        .unwrap();
+
+    let webhooks = webhooks(&mock);
+    assert_eq!(webhooks.len(), 1);
+    assert_eq!(webhooks[0]["host_name"], HOST_NAME);
+    assert_eq!(webhooks[0]["old_ip"], "198.51.100.1");
+    assert_eq!(webhooks[0]["new_ip"], EXTERNAL_IP);
+    assert_eq!(webhooks[0]["provider"], "Route 53");
+    assert!(webhooks[0]["timestamp"].as_u64().unwrap() > 0);
+    assert!(webhooks[0].get("error").is_none());
+}
+
+#[tokio::test]
+async fn does_not_notify_webhook_when_unchanged() {
+    let mock = mock(EXTERNAL_IP, StatusCode::OK);
+    let address = serve(&mock).await;
+    let webhook_url = format!("WEBHOOK_URL=http://{address}/webhook");
+    run_against(&mock, "webhook-unchanged", &[&webhook_url])
+        .await
+        .unwrap();
+
+    assert!(webhooks(&mock).is_empty());
+}
+
+/// The messages published to the ntfy topic.
+fn notifications(mock: &Mock) -> Vec<String> {
+    mock.requests
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(method, path, _)| method == Method::POST && path == "/dyndns")
+        .map(|(_, _, body)| body.clone())
+        .collect()
+}
+
+#[tokio::test]
+async fn publishes_change_to_ntfy_topic() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    let address = serve(&mock).await;
+    let ntfy_server = format!("NTFY_SERVER=http://{address}/");
+    run_against(&mock, "ntfy-changed", &["NTFY_TOPIC=dyndns", &ntfy_server])
+        .await
+        .unwrap();
+
+    assert_eq!(
+        notifications(&mock),
+        [format!("Route 53: 198.51.100.1 -> {EXTERNAL_IP}")]
+    );
+}
+
+#[tokio::test]
+async fn publishes_failure_to_ntfy_topic() {
+    let mock = mock("198.51.100.1", StatusCode::BAD_REQUEST);
+    let address = serve(&mock).await;
+    let ntfy_server = format!("NTFY_SERVER=http://{address}");
+    run_against(&mock, "ntfy-failed", &["NTFY_TOPIC=dyndns", &ntfy_server])
+        .await
+        .unwrap_err();
+
+    let notifications = notifications(&mock);
+    assert_eq!(notifications.len(), 1);
+    assert!(
+        notifications[0].starts_with("Route 53 error:"),
+        "{}",
+        notifications[0]
+    );
+}
+
+#[tokio::test]
+async fn publishes_nothing_to_ntfy_topic_when_unchanged() {
+    let mock = mock(EXTERNAL_IP, StatusCode::OK);
+    let address = serve(&mock).await;
+    let ntfy_server = format!("NTFY_SERVER=http://{address}");
+    run_against(
+        &mock,
+        "ntfy-unchanged",
+        &["NTFY_TOPIC=dyndns", &ntfy_server],
+    )
+    .await
+    .unwrap();
+
+    assert!(notifications(&mock).is_empty());
+}
+
+/// The paths of the requests to the healthcheck.
+fn pings(mock: &Mock) -> Vec<String> {
+    mock.requests
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(_, path, _)| path.starts_with("/ping"))
+        .map(|(_, path, _)| path.clone())
+        .collect()
+}
+
+#[tokio::test]
+async fn pings_healthcheck_after_success() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    let address = serve(&mock).await;
+    let healthcheck_url = format!("HEALTHCHECK_URL=http://{address}/ping");
+    run_against(&mock, "ping-success", &[&healthcheck_url])
+        .await
+        .unwrap();
+
+    assert_eq!(pings(&mock), ["/ping"]);
+}
+
+#[tokio::test]
+async fn pings_healthcheck_failure_after_failed_update() {
+    let mock = mock("198.51.100.1", StatusCode::BAD_REQUEST);
+    let address = serve(&mock).await;
+    let healthcheck_url = format!("HEALTHCHECK_URL=http://{address}/ping");
+    run_against(&mock, "ping-failure", &[&healthcheck_url])
+        .await
+        .unwrap_err();
+
+    assert_eq!(pings(&mock), ["/ping/fail"]);
+}
+
+/// The value of the metric in the Prometheus text format.
+fn metric(metrics: &str, name: &str) -> u64 {
+    metrics
+        .lines()
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix(' '))
+        .unwrap_or_else(|| panic!("{name} missing from {metrics}"))
+        .parse()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn serves_metrics_in_daemon_mode() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let metrics_port = format!("METRICS_PORT={port}");
+    let daemon = spawn_daemon(
+        &mock,
+        "daemon-metrics",
+        1,
+        &[&metrics_port, "MIN_UPDATE_INTERVAL=0"],
+    )
+    .await;
+    // The first run updates the record, and the next ones find it unchanged
+    wait_for_requests(&mock, 2).await;
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    let metrics = reqwest::get(format!("http://127.0.0.1:{port}/metrics"))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let output = terminate("daemon-metrics", daemon).await;
+
+    assert!(output.status.success());
+    assert_eq!(metric(&metrics, "dyndns_updates_total"), 1, "{metrics}");
+    // There was no address before the first run to change from
+    assert_eq!(metric(&metrics, "dyndns_ip_changes_total"), 0, "{metrics}");
+    assert!(
+        metric(&metrics, "dyndns_last_success_timestamp") > 0,
+        "{metrics}"
+    );
+    assert!(
+        !metrics.contains("dyndns_update_errors_total{"),
+        "{metrics}"
+    );
+}
+
+#[tokio::test]
+async fn uses_external_ip_without_detecting() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    run_against(&mock, "external-ip", &["EXTERNAL_IP=198.51.100.9"])
+        .await
+        .unwrap();
+
+    let changes = changes(&mock);
+    assert_eq!(changes.len(), 1);
+    assert!(changes[0].contains("<Value>198.51.100.9</Value>"));
+    // The IP detection service was never asked
+    assert!(mock.ip_headers.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn fails_on_invalid_external_ip() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    let e = run_against(&mock, "invalid-external-ip", &["EXTERNAL_IP=198.51.100"])
+        .await
+        .unwrap_err();
+
+    assert!(matches!(e, DNSUpdateError::Config(_)), "{e}");
+    assert!(e.to_string().contains("EXTERNAL_IP"), "{e}");
+    assert!(mock.requests.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn reports_status_of_each_host() {
+    let mock = mock(EXTERNAL_IP, StatusCode::OK);
+    let address = serve(&mock).await;
+    let state_file = state_file("host-statuses");
+    let config_file = env::temp_dir().join(format!(
+        "dyndns-rs-test-host-statuses-{}.toml",
+        std::process::id()
+    ));
+    std::fs::write(
+        &config_file,
+        format!(
+            "[[records]]\n\
+            host_name = \"broken.example.com\"\n\
+            provider = \"route53\"\n\
+            route53.hosted_zone_id = \"{FAILING_ZONE}\"\n"
+        ),
+    )
+    .unwrap();
+    // The mock only has a record for the first host
+    let mut args = args(
+        address,
+        &state_file,
+        &[
+            "HOST_NAME=home.example.com,other.example.com",
+            "OUTPUT=json",
+        ],
+    );
+    args.push(format!("--config={}", config_file.display()));
+    let output = output(args).await;
+    let _ = std::fs::remove_file(state_file);
+    let _ = std::fs::remove_file(config_file);
+
+    assert_eq!(output.status.code(), Some(4));
+    let summary: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let statuses: Vec<(&str, &str)> = summary["hosts"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|host| {
+            (
+                host["host_name"].as_str().unwrap(),
+                host["status"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        statuses,
+        [
+            ("home.example.com", "unchanged"),
+            ("other.example.com", "updated"),
+            ("broken.example.com", "failed"),
+        ]
+    );
+    let error = summary["hosts"][2]["error"].as_str().unwrap();
+    assert!(error.contains("InvalidChangeBatch"), "{error}");
+}
+
+#[tokio::test]
+async fn waits_for_recently_set_record_to_propagate() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    let address = serve(&mock).await;
+    set_credentials();
+    // The previous run set the record, but its state doesn't have the address
+    // as pushed everywhere, e.g. as another record failed
+    let state_file = state_file("propagating");
+    let mut state = SavedState::default();
+    state.remember(
+        vec![SetRecord::new(HOST_NAME, RecordType::A, EXTERNAL_IP)],
+        Duration::from_secs(600),
+    );
+    save_state(&state_file, &state).unwrap();
+    let mut args = args(address, &state_file, &["PROPAGATION_GRACE=600"]);
+    args.retain(|arg| arg != "--compare-source=provider");
+    // Nothing answers there, so resolving the host name would fail
+    args.extend([
+        String::from("--compare-source=resolver"),
+        String::from("--set=RESOLVER=127.0.0.1"),
+    ]);
+    let result = run(Args::parse_from(args)).await;
+    let _ = std::fs::remove_file(state_file);
+
+    result.unwrap();
+    assert!(mock.requests.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn applies_region_override_to_client() {
+    set_credentials();
+    let client = route53_client(None, None, Some(String::from("us-gov-west-1")), None)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        client.config().region().map(|region| region.as_ref()),
+        Some("us-gov-west-1")
+    );
+}
+
+#[tokio::test]
+async fn loads_named_profile() {
+    let config_file =
+        env::temp_dir().join(format!("dyndns-rs-test-aws-config-{}", std::process::id()));
+    std::fs::write(&config_file, "[profile dyndns-test]\nregion = eu-west-3\n").unwrap();
+    set_credentials();
+    env::set_var("AWS_CONFIG_FILE", &config_file);
+    let client = route53_client(Some("dyndns-test"), None, None, None).await;
+    let unknown = route53_client(Some("dyndns-missing"), None, None, None).await;
+    env::remove_var("AWS_CONFIG_FILE");
+    let _ = std::fs::remove_file(config_file);
+
+    assert_eq!(
+        client
+            .unwrap()
+            .config()
+            .region()
+            .map(|region| region.as_ref()),
+        Some("eu-west-3")
+    );
+    assert_eq!(
+        unknown.unwrap_err().to_string(),
+        "No AWS profile is named dyndns-missing"
+    );
+}
+
+/// Runs once against the mock, starting from the saved state.
+async fn run_from_state(
+    mock: &Arc<Mock>,
+    name: &str,
+    state: &SavedState,
+    settings: &[&str],
+) -> Result<(), DNSUpdateError> {
+    save_state(&state_file(name), state).unwrap();
+    run_against(mock, name, settings).await
+}
+
+/// A state where the external address was pushed to the host at Route 53.
+fn pushed_state() -> SavedState {
+    SavedState {
+        ips: vec![EXTERNAL_IP.parse().unwrap()],
+        pushed: vec![PushedRecord::new(
+            HOST_NAME,
+            Provider::Route53,
+            RecordType::A,
+            EXTERNAL_IP,
+        )],
+        ..SavedState::default()
+    }
+}
+
+#[tokio::test]
+async fn skips_host_address_was_pushed_to() {
+    // The record differs, but the state says it was already pushed
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    run_from_state(&mock, "pushed", &pushed_state(), &[])
+        .await
+        .unwrap();
+
+    assert!(mock.requests.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn updates_host_added_since_address_was_pushed() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    let host_names = format!("HOST_NAME={HOST_NAME},new.example.com");
+    run_from_state(&mock, "pushed-new-host", &pushed_state(), &[&host_names])
+        .await
+        .unwrap();
+
+    let changes = changes(&mock);
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].matches("<Change>").count(), 1);
+    assert!(changes[0].contains("<Name>new.example.com</Name>"));
+}
+
+/// A state where the address was pushed before, and another address was first
+/// detected the given number of seconds ago.
+fn pending_state(pending_ip: &str, seen_for: u64) -> SavedState {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    SavedState {
+        ips: vec!["198.51.100.1".parse().unwrap()],
+        pending: vec![PendingIp {
+            ip: pending_ip.parse().unwrap(),
+            first_seen: now - seen_for,
+        }],
+        ..SavedState::default()
+    }
+}
+
+#[tokio::test]
+async fn holds_back_flapping_address() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    // Another address was seen for long enough, but the address changed again
+    let state = pending_state("203.0.113.8", 600);
+    run_from_state(&mock, "flapping", &state, &["DEBOUNCE_SECONDS=60"])
+        .await
+        .unwrap();
+
+    assert!(changes(&mock).is_empty());
+}
+
+#[tokio::test]
+async fn commits_address_stable_for_long_enough() {
+    let mock = mock("198.51.100.1", StatusCode::OK);
+    let state = pending_state(EXTERNAL_IP, 120);
+    run_from_state(&mock, "stable", &state, &["DEBOUNCE_SECONDS=60"])
+        .await
+        .unwrap();
+
+    let changes = changes(&mock);
+    assert_eq!(changes.len(), 1);
+    assert!(changes[0].contains(&format!("<Value>{EXTERNAL_IP}</Value>")));
+}
@@ -0,0 +1,33 @@
+//! Aggregates the checks of the self-test into a checklist.
+
+use dyndns_rs::selftest::{checklist, failures, Check};
+
+fn checks() -> Vec<Check> {
+    vec![
+        (String::from("Configuration"), Ok(())),
+        (
+            String::from("IP detection from https://ip.example.com"),
+            Err(String::from("connection refused")),
+        ),
+        (String::from("Route 53 credentials"), Ok(())),
+        (String::from("DuckDNS credentials"), Err(String::from("KO"))),
+    ]
+}
+
+#[test]
+fn lists_each_check() {
+    assert_eq!(
+        checklist(&checks()),
+        "[ok]   Configuration\n\
+         [FAIL] IP detection from https://ip.example.com: connection refused\n\
+         [ok]   Route 53 credentials\n\
+         [FAIL] DuckDNS credentials: KO\n"
+    );
+}
+
+#[test]
+fn counts_failed_checks() {
+    assert_eq!(failures(&checks()), 2);
+    assert_eq!(failures(&checks()[..1]), 0);
+    assert_eq!(failures(&[]), 0);
+}
@@ -0,0 +1,315 @@
+//! Detects the external IP address from each kind of source, and combines
+//! delegated IPv6 prefixes with interface identifiers.
+
+use async_trait::async_trait;
+use dyndns_rs::resolver::AddressFamily;
+use dyndns_rs::sources::{
+    combine_prefix_suffix, is_global, FallbackSource, FixedSource, HttpFetcher, HttpSource,
+    IpError, IpSource, StunSource, UpnpGateway, UpnpSource,
+};
+use reqwest::header::HeaderMap;
+use std::net::{IpAddr, Ipv6Addr};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+fn combine(prefix: &str, prefix_length: u8, suffix: &str) -> Ipv6Addr {
+    combine_prefix_suffix(
+        prefix.parse().unwrap(),
+        prefix_length,
+        suffix.parse().unwrap(),
+    )
+}
+
+#[test]
+fn combines_64_bit_prefix_with_suffix() {
+    assert_eq!(
+        combine("2001:db8:1:2:aaaa:bbbb:cccc:dddd", 64, "::1:2:3:4"),
+        "2001:db8:1:2:1:2:3:4".parse::<Ipv6Addr>().unwrap()
+    );
+}
+
+#[test]
+fn combines_prefix_not_on_group_boundary() {
+    // The /60 keeps the top 4 bits of the fourth group
+    assert_eq!(
+        combine("2001:db8:1:fff2::1", 60, "::e:0:0:0:1"),
+        "2001:db8:1:fffe::1".parse::<Ipv6Addr>().unwrap()
+    );
+}
+
+#[test]
+fn combines_with_empty_or_full_prefix() {
+    assert_eq!(
+        combine("2001:db8::1", 0, "fd00::1"),
+        "fd00::1".parse::<Ipv6Addr>().unwrap()
+    );
+    assert_eq!(
+        combine("2001:db8::1", 128, "::"),
+        "2001:db8::1".parse::<Ipv6Addr>().unwrap()
+    );
+}
+
+#[test]
+fn ignores_suffix_bits_within_prefix() {
+    assert_eq!(
+        combine("2001:db8:1:2::", 64, "ffff::1"),
+        "2001:db8:1:2::1".parse::<Ipv6Addr>().unwrap()
+    );
+}
+
+/// A gateway answering with a canned SOAP response.
+struct StubGateway(&'static str);
+
+#[async_trait]
+impl UpnpGateway for StubGateway {
+    async fn get_external_ip_address(&self) -> Result<String, IpError> {
+        Ok(format!(
+            "<?xml version=\"1.0\"?>\
+            <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" \
+            s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\">\
+            <s:Body><u:GetExternalIPAddressResponse \
+            xmlns:u=\"urn:schemas-upnp-org:service:WANIPConnection:1\">\
+            <NewExternalIPAddress>{}</NewExternalIPAddress>\
+            </u:GetExternalIPAddressResponse></s:Body></s:Envelope>",
+            self.0
+        ))
+    }
+}
+
+#[tokio::test]
+async fn reads_upnp_gateway_address() {
+    let source = UpnpSource::new(Box::new(StubGateway("203.0.113.7")), AddressFamily::V4);
+
+    assert_eq!(
+        source.detect().await.unwrap(),
+        "203.0.113.7".parse::<IpAddr>().unwrap()
+    );
+}
+
+#[tokio::test]
+async fn fails_for_upnp_gateway_without_connection() {
+    let source = UpnpSource::new(Box::new(StubGateway("")), AddressFamily::Any);
+
+    let e = source.detect().await.unwrap_err();
+    assert!(matches!(e, IpError::AddrParse(_, _)), "{e}");
+}
+
+#[tokio::test]
+async fn fails_for_ipv6_from_upnp_gateway() {
+    let source = UpnpSource::new(Box::new(StubGateway("203.0.113.7")), AddressFamily::V6);
+
+    let e = source.detect().await.unwrap_err();
+    assert!(matches!(e, IpError::Upnp(_)), "{e}");
+}
+
+/// A fetcher answering every request with the status and body, failing on
+/// unsuccessful statuses as reqwest's client does.
+struct StubFetcher(u16, &'static str);
+
+#[async_trait]
+impl HttpFetcher for StubFetcher {
+    async fn get_text(&self, _url: &str, _headers: &HeaderMap) -> Result<String, IpError> {
+        let response = http::Response::builder()
+            .status(self.0)
+            .body(self.1)
+            .unwrap();
+        Ok(reqwest::Response::from(response)
+            .error_for_status()?
+            .text()
+            .await?)
+    }
+}
+
+fn http_source(fetcher: impl HttpFetcher + 'static, family: AddressFamily) -> HttpSource {
+    HttpSource::new(
+        Box::new(fetcher),
+        "https://ip.example.com",
+        family,
+        HeaderMap::new(),
+    )
+}
+
+#[tokio::test]
+async fn detects_address_in_body() {
+    let source = http_source(StubFetcher(200, "203.0.113.7\n"), AddressFamily::V4);
+
+    assert_eq!(
+        source.detect().await.unwrap(),
+        "203.0.113.7".parse::<IpAddr>().unwrap()
+    );
+}
+
+#[tokio::test]
+async fn fails_on_error_status() {
+    let source = http_source(StubFetcher(503, "203.0.113.7"), AddressFamily::Any);
+
+    let e = source.detect().await.unwrap_err();
+    assert!(
+        matches!(&e, IpError::Http(e) if e.status() == Some(reqwest::StatusCode::SERVICE_UNAVAILABLE)),
+        "{e}"
+    );
+}
+
+#[tokio::test]
+async fn fails_for_html_body() {
+    let source = http_source(StubFetcher(200, "<html>error</html>"), AddressFamily::Any);
+
+    let e = source.detect().await.unwrap_err();
+    assert!(
+        matches!(&e, IpError::AddrParse(body, _) if body == "<html>error</html>"),
+        "{e}"
+    );
+}
+
+#[tokio::test]
+async fn detects_ipv6_address() {
+    let source = http_source(StubFetcher(200, "2001:db8::7\n"), AddressFamily::V6);
+
+    assert_eq!(
+        source.detect().await.unwrap(),
+        "2001:db8::7".parse::<IpAddr>().unwrap()
+    );
+}
+
+#[tokio::test]
+async fn fails_for_ipv4_address_when_detecting_ipv6() {
+    // As when the service was reached over IPv4 through a proxy
+    let source = http_source(StubFetcher(200, "203.0.113.7"), AddressFamily::V6);
+
+    let e = source.detect().await.unwrap_err();
+    assert!(matches!(e, IpError::Family(IpAddr::V4(_))), "{e}");
+}
+
+/// A source of the address, failing unless it's IPv4.
+fn fixed_v4(ip: &str) -> Box<dyn IpSource> {
+    Box::new(FixedSource::new(ip.parse().unwrap(), AddressFamily::V4))
+}
+
+#[tokio::test]
+async fn falls_back_to_next_source() {
+    let source = FallbackSource::new(vec![fixed_v4("2001:db8::7"), fixed_v4("203.0.113.7")]);
+
+    let (ip, detected_by) = source.detect_from().await.unwrap();
+    assert_eq!(ip.to_string(), "203.0.113.7");
+    assert_eq!(detected_by, "EXTERNAL_IP=203.0.113.7");
+}
+
+#[tokio::test]
+async fn fails_when_every_source_fails() {
+    let source = FallbackSource::new(vec![fixed_v4("2001:db8::7"), fixed_v4("2001:db8::8")]);
+
+    let e = source.detect().await.unwrap_err();
+    let IpError::AllFailed(errors) = &e else {
+        panic!("expected every source to fail: {e}");
+    };
+    let sources: Vec<&str> = errors.iter().map(|(source, _)| source.as_str()).collect();
+    assert_eq!(
+        sources,
+        ["EXTERNAL_IP=2001:db8::7", "EXTERNAL_IP=2001:db8::8"]
+    );
+    assert!(matches!(errors[0].1, IpError::Fixed(_)), "{e}");
+}
+
+fn global(ip: &str) -> bool {
+    is_global(&ip.parse().unwrap())
+}
+
+#[test]
+fn public_addresses_are_global() {
+    assert!(global("8.8.8.8"));
+    assert!(global("2606:4700::1111"));
+}
+
+#[test]
+fn loopback_addresses_are_not_global() {
+    assert!(!global("127.0.0.1"));
+    assert!(!global("::1"));
+}
+
+#[test]
+fn link_local_addresses_are_not_global() {
+    assert!(!global("169.254.1.1"));
+    assert!(!global("fe80::1"));
+}
+
+#[test]
+fn private_addresses_are_not_global() {
+    assert!(!global("192.168.1.1"));
+    assert!(!global("100.64.0.1"));
+    // Unique local addresses
+    assert!(!global("fc00::1"));
+    assert!(!global("fd12:3456::1"));
+}
+
+const MAGIC_COOKIE: [u8; 4] = [0x21, 0x12, 0xa4, 0x42];
+
+/// Answers each STUN binding request on a free local port with a binding
+/// success response holding the attributes made for its transaction ID,
+/// returning the address of the server.
+async fn serve_stun(attributes: fn(&[u8]) -> Vec<u8>) -> String {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let address = socket.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut request = [0; 576];
+        loop {
+            let (_, peer) = socket.recv_from(&mut request).await.unwrap();
+            let attributes = attributes(&request[8..20]);
+            let mut response = vec![0x01, 0x01];
+            response.extend_from_slice(&(attributes.len() as u16).to_be_bytes());
+            // The cookie and transaction ID of the request
+            response.extend_from_slice(&request[4..20]);
+            response.extend_from_slice(&attributes);
+            socket.send_to(&response, peer).await.unwrap();
+        }
+    });
+    address.to_string()
+}
+
+/// An XOR-MAPPED-ADDRESS attribute of the address and port 32853, as in the
+/// test vectors of RFC 5769.
+fn xor_mapped_address(ip: &str, transaction_id: &[u8]) -> Vec<u8> {
+    let (family, octets) = match ip.parse().unwrap() {
+        IpAddr::V4(ip) => (1, ip.octets().to_vec()),
+        IpAddr::V6(ip) => (2, ip.octets().to_vec()),
+    };
+    let mask = MAGIC_COOKIE.iter().chain(transaction_id);
+    let mut attribute = vec![0x00, 0x20, 0x00, 4 + octets.len() as u8];
+    attribute.extend_from_slice(&[0x00, family, 0xa1, 0x47]);
+    attribute.extend(octets.iter().zip(mask).map(|(octet, mask)| octet ^ mask));
+    attribute
+}
+
+async fn stun_detect(server: &str) -> Result<IpAddr, IpError> {
+    StunSource::new(server, AddressFamily::Any, Duration::from_secs(1))
+        .detect()
+        .await
+}
+
+#[tokio::test]
+async fn stun_reads_xor_mapped_ipv4_address() {
+    let server = serve_stun(|transaction_id| xor_mapped_address("192.0.2.1", transaction_id)).await;
+
+    let ip = stun_detect(&server).await.unwrap();
+    assert_eq!(ip.to_string(), "192.0.2.1");
+}
+
+#[tokio::test]
+async fn stun_reads_xor_mapped_ipv6_address() {
+    let server = serve_stun(|transaction_id| {
+        xor_mapped_address("2001:db8:1234:5678:11:2233:4455:6677", transaction_id)
+    })
+    .await;
+
+    let ip = stun_detect(&server).await.unwrap();
+    assert_eq!(ip.to_string(), "2001:db8:1234:5678:11:2233:4455:6677");
+}
+
+#[tokio::test]
+async fn stun_fails_on_truncated_attribute() {
+    // The attribute claims 8 bytes but only holds the family and port
+    let server = serve_stun(|_| vec![0x00, 0x20, 0x00, 0x08, 0x00, 0x01, 0xa1, 0x47]).await;
+
+    let e = stun_detect(&server).await.unwrap_err();
+    assert!(matches!(e, IpError::Stun(_)), "{e}");
+    assert!(e.to_string().contains("truncated attribute"), "{e}");
+}
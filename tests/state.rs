@@ -0,0 +1,124 @@
+//! Saves and loads the state kept between runs.
+
+use dyndns_rs::providers::{Provider, RecordType};
+use dyndns_rs::state::{load_state, save_state, PushedRecord, SetRecord, State};
+use std::env;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+fn state_file(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("dyndns-rs-test-{name}-{}.json", std::process::id()))
+}
+
+#[test]
+fn loads_saved_state() {
+    let ip: IpAddr = "203.0.113.7".parse().unwrap();
+    let mut state = State::default();
+    state.set_ips(vec![ip], Vec::new());
+    state.remember(
+        vec![SetRecord::new(
+            "home.example.com",
+            RecordType::A,
+            "203.0.113.7",
+        )],
+        Duration::from_secs(60),
+    );
+    state.observe("203.0.113.8".parse().unwrap());
+    let path = state_file("state-round-trip");
+    save_state(&path, &state).unwrap();
+    let loaded = load_state(&path);
+    let _ = std::fs::remove_file(path);
+
+    let loaded = loaded.unwrap();
+    assert_eq!(loaded.ips, [ip]);
+    assert_eq!(loaded.updated_at, state.updated_at);
+    assert!(loaded.recently_set(
+        "home.example.com",
+        RecordType::A,
+        "203.0.113.7",
+        Duration::from_secs(60)
+    ));
+    assert_eq!(loaded.pending.len(), 1);
+    assert_eq!(
+        loaded.pending[0].ip,
+        "203.0.113.8".parse::<IpAddr>().unwrap()
+    );
+}
+
+#[test]
+fn ignores_corrupt_state_file() {
+    let path = state_file("state-corrupt");
+    std::fs::write(&path, "{\"ips\": [\"203.0.113").unwrap();
+    let loaded = load_state(&path);
+    let _ = std::fs::remove_file(path);
+
+    assert!(loaded.is_none());
+}
+
+#[test]
+fn ignores_missing_state_file() {
+    assert!(load_state(&state_file("state-missing")).is_none());
+}
+
+#[test]
+fn loads_state_from_before_records_were_kept() {
+    let path = state_file("state-old");
+    std::fs::write(&path, "{\"ips\": [\"203.0.113.7\"], \"updated_at\": 1}").unwrap();
+    let loaded = load_state(&path);
+    let _ = std::fs::remove_file(path);
+
+    let loaded = loaded.unwrap();
+    assert_eq!(loaded.updated_at, 1);
+    assert!(loaded.records.is_empty());
+    assert!(loaded.pending.is_empty());
+}
+
+#[test]
+fn remembers_where_address_was_pushed() {
+    let ip: IpAddr = "203.0.113.7".parse().unwrap();
+    let mut state = State::default();
+    state.set_ips(
+        vec![ip],
+        vec![PushedRecord::new(
+            "home.example.com",
+            Provider::Route53,
+            RecordType::A,
+            "203.0.113.7",
+        )],
+    );
+    // Pushing the address to another host keeps the first
+    state.set_ips(
+        vec![ip],
+        vec![PushedRecord::new(
+            "new.example.com",
+            Provider::Route53,
+            RecordType::A,
+            "203.0.113.7",
+        )],
+    );
+
+    assert!(state.was_pushed("home.example.com", Provider::Route53, ip));
+    assert!(state.was_pushed("new.example.com", Provider::Route53, ip));
+    assert!(!state.was_pushed("home.example.com", Provider::DuckDns, ip));
+    assert!(!state.was_pushed("other.example.com", Provider::Route53, ip));
+}
+
+#[test]
+fn forgets_where_older_address_was_pushed() {
+    let mut state = State::default();
+    state.set_ips(
+        vec!["203.0.113.7".parse().unwrap()],
+        vec![PushedRecord::new(
+            "home.example.com",
+            Provider::Route53,
+            RecordType::A,
+            "203.0.113.7",
+        )],
+    );
+    let ip: IpAddr = "203.0.113.8".parse().unwrap();
+    state.set_ips(vec![ip], Vec::new());
+
+    assert!(state.pushed.is_empty());
+    assert!(!state.was_pushed("home.example.com", Provider::Route53, ip));
+}